@@ -0,0 +1,116 @@
+/// Automatic Frequency Correction settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AfcConfig {
+    /// Maximum correction applied to the channel frequency (in Hz)
+    pub max_offset_hz: i32,
+    /// Maximum change of the correction on a single packet (in Hz)
+    pub max_step_hz: i32,
+    /// Weight of a new measurement in the running offset, as a power of two:
+    /// 0 applies the full measurement, 2 applies a quarter, ... (up to 31)
+    pub smoothing: u8,
+    /// Measurements on packets with an SNR below this threshold are ignored (in 0.25dB steps)
+    pub min_snr_quarter_db: i16,
+}
+
+impl Default for AfcConfig {
+    /// Up to 20ppm at 868MHz, moving slowly
+    fn default() -> Self {
+        Self {
+            max_offset_hz: 17_000,
+            max_step_hz: 2_000,
+            smoothing: 2,
+            min_snr_quarter_db: -40,
+        }
+    }
+}
+
+/// Automatic Frequency Correction: keep a running estimate of the drift of the LR2021 crystal
+/// Measurements must be taken against a single stable reference: averaging the errors
+/// of several remote devices would track their crystals instead of the local one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Afc {
+    config: AfcConfig,
+    offset_hz: i32,
+}
+
+impl Afc {
+    /// Create the correction from its settings, limited to their valid range
+    pub fn new(mut config: AfcConfig) -> Self {
+        config.smoothing = config.smoothing.min(31);
+        config.max_step_hz = config.max_step_hz.max(0);
+        config.max_offset_hz = config.max_offset_hz.max(0);
+        Self { config, offset_hz: 0 }
+    }
+
+    pub fn config(&self) -> &AfcConfig {
+        &self.config
+    }
+
+    /// Current correction applied to the channel frequency (in Hz)
+    pub fn offset_hz(&self) -> i32 {
+        self.offset_hz
+    }
+
+    /// Restart from no correction
+    pub fn reset(&mut self) {
+        self.offset_hz = 0;
+    }
+
+    /// Update the running offset with the frequency error measured on a packet received
+    /// on a channel already corrected by the current offset
+    /// (positive when the packet was received above the corrected channel). Return the new offset
+    pub fn update(&mut self, freq_offset_hz: i32, snr_quarter_db: i16) -> i32 {
+        if snr_quarter_db < self.config.min_snr_quarter_db {
+            return self.offset_hz;
+        }
+        let step = (freq_offset_hz >> self.config.smoothing)
+            .clamp(-self.config.max_step_hz, self.config.max_step_hz);
+        self.offset_hz = (self.offset_hz + step)
+            .clamp(-self.config.max_offset_hz, self.config.max_offset_hz);
+        self.offset_hz
+    }
+
+    /// Channel frequency corrected by the current offset
+    pub fn apply(&self, frequency_in_hz: u32) -> u32 {
+        frequency_in_hz.saturating_add_signed(self.offset_hz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_smoothing() {
+        let mut afc = Afc::new(AfcConfig::default());
+        assert_eq!(afc.update(4000, 0), 1000);
+        assert_eq!(afc.update(-400, 0), 900);
+        assert_eq!(afc.apply(868_100_000), 868_100_900);
+    }
+
+    #[test]
+    fn update_limits() {
+        let mut afc = Afc::new(AfcConfig::default());
+        // Low SNR measurement ignored
+        assert_eq!(afc.update(4000, -41), 0);
+        // Step limited to 2kHz, offset to 17kHz
+        assert_eq!(afc.update(-20_000, 0), -2000);
+        for _ in 0..10 {
+            afc.update(-20_000, 0);
+        }
+        assert_eq!(afc.offset_hz(), -17_000);
+        assert_eq!(afc.apply(868_100_000), 868_083_000);
+        afc.reset();
+        assert_eq!(afc.offset_hz(), 0);
+    }
+
+    #[test]
+    fn smoothing_range() {
+        let mut afc = Afc::new(AfcConfig { smoothing: 40, ..AfcConfig::default() });
+        assert_eq!(afc.config().smoothing, 31);
+        assert_eq!(afc.update(4000, 0), 0);
+        assert_eq!(afc.update(-4000, 0), -1);
+    }
+}
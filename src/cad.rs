@@ -0,0 +1,77 @@
+use lora_phy::mod_params::{Bandwidth, SpreadingFactor};
+use lr2021::lora::ExitMode;
+
+/// Channel Activity Detection parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CadParams {
+    /// Number of symbols used for the detection (1 to 15)
+    pub nb_symbols: u8,
+    /// Detect any LoRa symbol instead of only preamble symbols
+    pub pbl_any: bool,
+    /// Fast detection: stop early when the peak-to-noise ratio is above the threshold by this delta (0 to disable)
+    pub pnr_delta: u8,
+    /// Detection peak threshold: None to use the chip default
+    pub det_peak: Option<u8>,
+}
+
+/// Function providing CAD parameters for a given modulation
+pub type CadParamsFn = fn(SpreadingFactor, Bandwidth) -> CadParams;
+
+impl CadParams {
+    /// Default CAD parameters for a spreading factor and bandwidth
+    ///
+    /// The detection peak is left to the chip, which adapts it to the spreading factor:
+    /// set `det_peak` explicitly to trade missed detections against false ones.
+    /// Narrow bandwidths use more symbols to avoid missed detections.
+    pub fn recommended(_sf: SpreadingFactor, bw: Bandwidth) -> Self {
+        let nb_symbols = match bw {
+            Bandwidth::_500KHz | Bandwidth::_250KHz | Bandwidth::_125KHz => 4,
+            _ => 8,
+        };
+        Self { nb_symbols, pbl_any: false, pnr_delta: 10, det_peak: None }
+    }
+}
+
+/// Action performed by the chip at the end of a CAD
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CadExit {
+    /// Go back to standby and report the detection
+    CadOnly,
+    /// Listen-Before-Talk: transmit if no activity was detected
+    Lbt,
+    /// Start reception if activity was detected
+    Rx,
+}
+
+impl From<CadExit> for ExitMode {
+    fn from(value: CadExit) -> Self {
+        match value {
+            CadExit::CadOnly => ExitMode::CadOnly,
+            CadExit::Lbt => ExitMode::CadLbt,
+            CadExit::Rx => ExitMode::CadRx,
+        }
+    }
+}
+
+/// Stop condition of a CAD scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CadScanMode {
+    /// Stop on the first detection
+    First,
+    /// Scan all channels and spreading factors
+    All,
+    /// Stop on the first detection and stay in reception on this channel:
+    /// the timeout is expressed in symbols (0 for none).
+    /// Packet parameters must have been set before the scan.
+    FirstThenRx { rx_timeout_symbols: u16 },
+}
+
+/// Activity detected during a CAD scan
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CadDetection {
+    pub freq_hz: u32,
+    pub spreading_factor: SpreadingFactor,
+}
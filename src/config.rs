@@ -0,0 +1,240 @@
+use lr2021::system::DioNum;
+pub use lr2021::system::TcxoVoltage;
+
+/// Regulator used to power the LR2021 digital and analog blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Regulator {
+    /// Linear regulator only
+    #[default]
+    Ldo,
+    /// DC-DC converter (SIMO), requires the inductor to be populated
+    DcDc,
+}
+
+/// Power amplifier used for transmission
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PaSelect {
+    /// Select PA from the channel frequency
+    #[default]
+    Auto,
+    /// Low-frequency PA (sub-GHz)
+    Lf,
+    /// High-frequency PA (2.4GHz)
+    Hf,
+}
+
+/// TCXO supply configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TcxoConfig {
+    /// Voltage provided by the LR2021 on the VTCXO pin
+    pub voltage: TcxoVoltage,
+    /// Time for the TCXO to be stable after startup (in us)
+    pub startup_us: u32,
+}
+
+/// Crystal oscillator trimming capacitors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct XoscTrim {
+    /// Capacitor on XTA (0 to 47, 0.47pF step from 11.3pF)
+    pub xta: u8,
+    /// Capacitor on XTB (0 to 47, 0.47pF step from 10.1pF)
+    pub xtb: u8,
+    /// Additional time for the crystal to stabilize at startup (in us)
+    pub delay_us: Option<u8>,
+}
+
+/// Level of a DIO configured as RF switch control in each chip mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RfSwitchLevels {
+    pub standby: bool,
+    pub rx_lf: bool,
+    pub tx_lf: bool,
+    pub rx_hf: bool,
+    pub tx_hf: bool,
+}
+
+/// RF switch description: DIOs driven high for each radio path
+/// DIOs of the table not listed for the active path are driven low.
+/// The DIO used for the IRQ must not be part of the table.
+/// CAD uses the RX path of the band selected by the last `set_channel`.
+///
+/// ```ignore
+/// const RF_SWITCH: RfSwitchTable = RfSwitchTable {
+///     rx_lf: &[DioNum::Dio5],
+///     tx_lf: &[DioNum::Dio6],
+///     rx_hf: &[DioNum::Dio7],
+///     tx_hf: &[DioNum::Dio7, DioNum::Dio8],
+///     ..RfSwitchTable::new()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RfSwitchTable {
+    pub standby: &'static [DioNum],
+    pub rx_lf: &'static [DioNum],
+    pub tx_lf: &'static [DioNum],
+    pub rx_hf: &'static [DioNum],
+    pub tx_hf: &'static [DioNum],
+}
+
+impl Default for RfSwitchTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RfSwitchTable {
+    /// Empty table: all DIOs low in every mode
+    pub const fn new() -> Self {
+        Self { standby: &[], rx_lf: &[], tx_lf: &[], rx_hf: &[], tx_hf: &[] }
+    }
+
+    /// Levels of each DIO (DIO5 to DIO11) used by the table
+    pub fn levels(&self) -> [Option<RfSwitchLevels>; NB_RF_SWITCH_DIO] {
+        let mut levels = [None; NB_RF_SWITCH_DIO];
+        type SetLevel = fn(&mut RfSwitchLevels);
+        let paths: [(&[DioNum], SetLevel); 5] = [
+            (self.standby, |l| l.standby = true),
+            (self.rx_lf, |l| l.rx_lf = true),
+            (self.tx_lf, |l| l.tx_lf = true),
+            (self.rx_hf, |l| l.rx_hf = true),
+            (self.tx_hf, |l| l.tx_hf = true),
+        ];
+        for (dios, set) in paths {
+            for idx in dios.iter().map(|d| rf_switch_idx(*d)) {
+                set(levels[idx].get_or_insert_with(RfSwitchLevels::default));
+            }
+        }
+        levels
+    }
+}
+
+/// Number of DIOs usable as RF switch control (DIO5 to DIO11)
+pub const NB_RF_SWITCH_DIO: usize = 7;
+
+/// Index of a DIO in the RF switch table
+pub(crate) fn rf_switch_idx(dio: DioNum) -> usize {
+    match dio {
+        DioNum::Dio5  => 0,
+        DioNum::Dio6  => 1,
+        DioNum::Dio7  => 2,
+        DioNum::Dio8  => 3,
+        DioNum::Dio9  => 4,
+        DioNum::Dio10 => 5,
+        DioNum::Dio11 => 6,
+    }
+}
+
+/// DIO associated to an index in the RF switch table
+pub(crate) const RF_SWITCH_DIOS: [DioNum; NB_RF_SWITCH_DIO] = [
+    DioNum::Dio5, DioNum::Dio6, DioNum::Dio7, DioNum::Dio8, DioNum::Dio9, DioNum::Dio10, DioNum::Dio11,
+];
+
+/// Maximum number of frequency bands in the channel plan
+pub const MAX_CALIB_BANDS: usize = 4;
+
+/// Board configuration of the LR2021, applied during `init_lora`
+///
+/// ```
+/// use lr2021::system::DioNum;
+/// use lr2021_loraphy::{Lr2021LoraPhyConfig, Regulator, RfSwitchLevels, TcxoVoltage};
+///
+/// let config = Lr2021LoraPhyConfig::new()
+///     .tcxo(TcxoVoltage::Tcxo1v8, 5000)
+///     .regulator(Regulator::DcDc)
+///     .rf_switch(DioNum::Dio5, RfSwitchLevels { rx_lf: true, ..Default::default() })
+///     .rf_switch(DioNum::Dio6, RfSwitchLevels { tx_lf: true, ..Default::default() });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Lr2021LoraPhyConfig {
+    pub tcxo: Option<TcxoConfig>,
+    pub regulator: Regulator,
+    pub xosc_trim: Option<XoscTrim>,
+    pub rf_switch: [Option<RfSwitchLevels>; NB_RF_SWITCH_DIO],
+    pub pa: PaSelect,
+    /// Frequency bands (min, max in Hz) of the channel plan: the image calibration
+    /// covers a whole band at once when a channel inside is selected
+    pub calib_bands: [Option<(u32, u32)>; MAX_CALIB_BANDS],
+    /// LoRa syncword (two 5-bit symbols) used instead of the one provided by lora-phy to `init_lora`
+    pub syncword: Option<(u8, u8)>,
+}
+
+impl Default for Lr2021LoraPhyConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lr2021LoraPhyConfig {
+    /// Default configuration: crystal oscillator, LDO, no RF switch, PA selected from frequency
+    pub const fn new() -> Self {
+        Self {
+            tcxo: None,
+            regulator: Regulator::Ldo,
+            xosc_trim: None,
+            rf_switch: [None; NB_RF_SWITCH_DIO],
+            pa: PaSelect::Auto,
+            calib_bands: [None; MAX_CALIB_BANDS],
+            syncword: None,
+        }
+    }
+
+    /// Use a TCXO supplied by the LR2021
+    pub const fn tcxo(mut self, voltage: TcxoVoltage, startup_us: u32) -> Self {
+        self.tcxo = Some(TcxoConfig { voltage, startup_us });
+        self
+    }
+
+    /// Select the regulator
+    pub const fn regulator(mut self, regulator: Regulator) -> Self {
+        self.regulator = regulator;
+        self
+    }
+
+    /// Set the crystal trimming capacitors, with an optional additional startup delay
+    pub const fn xosc_trim(mut self, xta: u8, xtb: u8, delay_us: Option<u8>) -> Self {
+        self.xosc_trim = Some(XoscTrim { xta, xtb, delay_us });
+        self
+    }
+
+    /// Use a DIO (DIO5 to DIO11) to control an RF switch
+    /// The DIO used for the IRQ cannot be part of the RF switch: `init_lora` would fail
+    pub fn rf_switch(mut self, dio: DioNum, levels: RfSwitchLevels) -> Self {
+        self.rf_switch[rf_switch_idx(dio)] = Some(levels);
+        self
+    }
+
+    /// Describe the RF switch with a table of DIOs per radio path
+    /// Replaces any DIO previously set with `rf_switch`
+    pub fn rf_switch_table(mut self, table: RfSwitchTable) -> Self {
+        self.rf_switch = table.levels();
+        self
+    }
+
+    /// Select the power amplifier
+    pub const fn pa(mut self, pa: PaSelect) -> Self {
+        self.pa = pa;
+        self
+    }
+
+    /// Use a custom LoRa syncword (e.g. `syncword_from_sx126x(0x2444)` or `syncword_ext(0x24)`)
+    /// instead of the public/private one selected by lora-phy
+    pub const fn syncword(mut self, syncword: (u8, u8)) -> Self {
+        self.syncword = Some(syncword);
+        self
+    }
+
+    /// Add a frequency band of the channel plan (e.g. 902-928MHz for US915)
+    /// Bands above `MAX_CALIB_BANDS` are ignored
+    pub fn calib_band(mut self, min_hz: u32, max_hz: u32) -> Self {
+        if let Some(band) = self.calib_bands.iter_mut().find(|b| b.is_none()) {
+            *band = Some((min_hz.min(max_hz), max_hz.max(min_hz)));
+        }
+        self
+    }
+}
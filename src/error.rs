@@ -0,0 +1,169 @@
+use lora_phy::mod_params::RadioError;
+use lr2021::Lr2021Error;
+
+/// Operation performed by the wrapper when an error occured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PhyOp {
+    Reset,
+    WaitReady,
+    WakeUp,
+    InitCalib,
+    InitPacketType,
+    InitSyncword,
+    SetChipMode,
+    SetTxParams,
+    SetModulation,
+    SetPacket,
+    CalibImage,
+    SetChannel,
+    SetPayload,
+    SetTx,
+    SetRx,
+    GetRxPktLen,
+    ReadRxFifo,
+    GetPacketStatus,
+    SetCadParams,
+    SetCad,
+    SetTxTest,
+    GetRssi,
+    SetDioIrq,
+    GetStatus,
+    ClearIrq,
+    SetRegulator,
+    SetTcxo,
+    SetXoscTrim,
+    SetDioFunction,
+    SetRfSwitch,
+    SetPa,
+    ClearRxFifo,
+    SetSideDet,
+    SetSideDetSyncword,
+    SetTimestampSource,
+    GetTimestamp,
+    SetRangingAddr,
+    SetRangingDelay,
+    GetRangingResult,
+    SetRangingParams,
+    SetLrFhssSyncword,
+    BuildLrFhssFrame,
+    SetRangingPacketType,
+    PatchRangingRf,
+    SetLrFhssPacketType,
+    SetSyncword,
+    SetRangingModulation,
+}
+
+impl PhyOp {
+    /// Code reported in `RadioError::OpError` for this operation
+    pub fn code(&self) -> u8 {
+        match self {
+            PhyOp::InitCalib       => 0,
+            PhyOp::InitPacketType  => 1,
+            PhyOp::InitSyncword    => 2,
+            PhyOp::CalibImage      => 3,
+            PhyOp::SetChannel      => 4,
+            PhyOp::SetPayload      => 5,
+            PhyOp::SetTx           => 6,
+            PhyOp::SetRx           => 7,
+            PhyOp::GetRxPktLen     => 8,
+            PhyOp::ReadRxFifo      => 9,
+            PhyOp::GetPacketStatus => 10,
+            PhyOp::SetCadParams    => 11,
+            PhyOp::SetCad          => 12,
+            PhyOp::GetRssi         => 13,
+            PhyOp::ClearIrq        => 14,
+            PhyOp::GetStatus       => 15,
+            PhyOp::SetDioIrq       => 16,
+            PhyOp::SetTxTest       => 17,
+            PhyOp::Reset           => 18,
+            PhyOp::WaitReady       => 19,
+            PhyOp::WakeUp          => 20,
+            PhyOp::SetChipMode     => 21,
+            PhyOp::SetTxParams     => 22,
+            PhyOp::SetModulation   => 23,
+            PhyOp::SetPacket       => 24,
+            PhyOp::SetRegulator    => 25,
+            PhyOp::SetTcxo         => 26,
+            PhyOp::SetXoscTrim     => 27,
+            PhyOp::SetDioFunction  => 28,
+            PhyOp::SetRfSwitch     => 29,
+            PhyOp::SetPa           => 30,
+            PhyOp::ClearRxFifo     => 31,
+            PhyOp::SetSideDet      => 32,
+            PhyOp::SetSideDetSyncword => 33,
+            PhyOp::SetTimestampSource => 34,
+            PhyOp::GetTimestamp    => 35,
+            PhyOp::SetRangingAddr  => 36,
+            PhyOp::SetRangingDelay => 37,
+            PhyOp::GetRangingResult => 38,
+            PhyOp::SetRangingParams => 39,
+            PhyOp::SetLrFhssSyncword => 40,
+            PhyOp::BuildLrFhssFrame => 41,
+            PhyOp::SetRangingPacketType => 42,
+            PhyOp::PatchRangingRf  => 43,
+            PhyOp::SetLrFhssPacketType => 44,
+            PhyOp::SetSyncword     => 45,
+            PhyOp::SetRangingModulation => 46,
+        }
+    }
+}
+
+/// Error reported by the LR2021 LoRaPhy wrapper
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PhyError {
+    /// Driver command failed (SPI, busy timeout, command status, ...)
+    Driver { op: PhyOp, err: Lr2021Error },
+    /// IRQ pin could not be read
+    IrqPin,
+    /// Packet received with a wrong CRC
+    /// Reported as `RadioError::OpError(0x80)`
+    Crc,
+    /// LoRa explicit header could not be decoded
+    /// Reported as `RadioError::OpError(0x81)`
+    Header,
+    /// Listen-Before-Talk detected activity: packet was not transmitted
+    /// Reported as `RadioError::OpError(0x82)`
+    ChannelBusy,
+}
+
+impl PhyError {
+    /// Operation which failed, if any
+    pub fn op(&self) -> Option<PhyOp> {
+        match self {
+            PhyError::Driver { op, .. } => Some(*op),
+            _ => None,
+        }
+    }
+
+    /// Underlying LR2021 driver error, if any
+    pub fn driver_error(&self) -> Option<Lr2021Error> {
+        match self {
+            PhyError::Driver { err, .. } => Some(*err),
+            _ => None,
+        }
+    }
+}
+
+impl From<PhyError> for RadioError {
+    fn from(value: PhyError) -> Self {
+        match value {
+            PhyError::Driver { op, .. } => match op {
+                PhyOp::Reset => RadioError::Reset,
+                PhyOp::WaitReady |
+                PhyOp::WakeUp => RadioError::DIO1,
+                PhyOp::SetChipMode => RadioError::SPI,
+                PhyOp::SetTxParams |
+                PhyOp::SetModulation |
+                PhyOp::SetPa |
+                PhyOp::SetPacket => RadioError::InvalidConfiguration,
+                op => RadioError::OpError(op.code()),
+            },
+            PhyError::IrqPin => RadioError::Irq,
+            PhyError::Crc => RadioError::OpError(0x80),
+            PhyError::Header => RadioError::OpError(0x81),
+            PhyError::ChannelBusy => RadioError::OpError(0x82),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+#![macro_use]
+#![allow(unused_macros)]
+
+#[cfg(not(feature = "defmt"))]
+use core::fmt;
+
+use lora_phy::{mod_params::RadioMode, RxMode};
+use lr2021::status::Intr;
+
+#[cfg(all(feature = "defmt", feature = "log"))]
+compile_error!("You may not enable both `defmt` and `log` features.");
+
+macro_rules! trace {
+    ($s:literal $(, $x:expr)* $(,)?) => {
+        {
+            #[cfg(feature = "log")]
+            ::log::trace!($s $(, $x)*);
+            #[cfg(feature = "defmt")]
+            ::defmt::trace!($s $(, $x)*);
+            #[cfg(not(any(feature = "log", feature = "defmt")))]
+            let _ = ($( & $x ),*);
+        }
+    };
+}
+
+macro_rules! debug {
+    ($s:literal $(, $x:expr)* $(,)?) => {
+        {
+            #[cfg(feature = "log")]
+            ::log::debug!($s $(, $x)*);
+            #[cfg(feature = "defmt")]
+            ::defmt::debug!($s $(, $x)*);
+            #[cfg(not(any(feature = "log", feature = "defmt")))]
+            let _ = ($( & $x ),*);
+        }
+    };
+}
+
+macro_rules! warn {
+    ($s:literal $(, $x:expr)* $(,)?) => {
+        {
+            #[cfg(feature = "log")]
+            ::log::warn!($s $(, $x)*);
+            #[cfg(feature = "defmt")]
+            ::defmt::warn!($s $(, $x)*);
+            #[cfg(not(any(feature = "log", feature = "defmt")))]
+            let _ = ($( & $x ),*);
+        }
+    };
+}
+
+/// Name of the IRQ flags set in an interrupt status, for tracing with `log`
+/// (lr2021 already formats `Intr` with defmt)
+#[cfg(not(feature = "defmt"))]
+pub(crate) struct IrqFlags(pub Intr);
+
+#[cfg(not(feature = "defmt"))]
+impl fmt::Display for IrqFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let intr = &self.0;
+        let names = [
+            (intr.tx_done(), "TxDone"),
+            (intr.rx_done(), "RxDone"),
+            (intr.timeout(), "Timeout"),
+            (intr.preamble_detected(), "PreambleDetected"),
+            (intr.header_valid(), "HeaderValid"),
+            (intr.header_err(), "HeaderErr"),
+            (intr.crc_error(), "CrcError"),
+            (intr.cad_done(), "CadDone"),
+            (intr.cad_detected(), "CadDetected"),
+        ];
+        f.write_str("[")?;
+        for (i, name) in names.into_iter().filter_map(|(set, name)| set.then_some(name)).enumerate() {
+            if i > 0 {
+                f.write_str("|")?;
+            }
+            f.write_str(name)?;
+        }
+        f.write_str("]")
+    }
+}
+
+/// Interrupt status in a form accepted by the trace macros
+#[cfg(not(feature = "defmt"))]
+pub(crate) fn irq_flags(intr: Intr) -> IrqFlags {
+    IrqFlags(intr)
+}
+
+/// Interrupt status in a form accepted by the trace macros
+#[cfg(feature = "defmt")]
+pub(crate) fn irq_flags(intr: Intr) -> Intr {
+    intr
+}
+
+/// Name of a lora-phy radio mode, for tracing
+pub(crate) fn radio_mode_name(mode: RadioMode) -> &'static str {
+    match mode {
+        RadioMode::Sleep => "Sleep",
+        RadioMode::Standby => "Standby",
+        RadioMode::FrequencySynthesis => "FrequencySynthesis",
+        RadioMode::Transmit => "Transmit",
+        RadioMode::Receive(RxMode::Continuous) => "Receive(Continuous)",
+        RadioMode::Receive(RxMode::Single(_)) => "Receive(Single)",
+        RadioMode::Receive(RxMode::DutyCycle(_)) => "Receive(DutyCycle)",
+        RadioMode::ChannelActivityDetection => "ChannelActivityDetection",
+    }
+}
@@ -1,18 +1,173 @@
 #![no_std]
 
-use lr2021::{BusyAsync, BusyPin, Lr2021, lora::{ExitMode, HeaderType, Ldro, LoraBw, LoraCr, LoraModulationParams, LoraPacketParams, Sf}, radio::{PacketType, RampTime}, status::Intr, system::{ChipMode, DioNum}};
+// This mod MUST go first, so that the others see its macros.
+mod fmt;
+
+use lr2021::{BusyAsync, BusyPin, Lr2021, Lr2021Error, lora::{HeaderType, Ldro, LoraBw, LoraCr, LoraModulationParams, LoraPacketParams, Sf, SidedetCfg}, radio::{PaLfMode, PacketType, TestMode}, status::Intr, system::{ChipMode, DioFunc, DioNum, PullDrive}};
 use embedded_hal::digital::{OutputPin, InputPin};
 use embedded_hal_async::{digital::Wait, spi::SpiBus};
-use embassy_time::Duration;
+use embassy_time::{Duration, Instant, Timer};
 
 pub use lora_phy::{mod_traits::*, mod_params::*, RxMode};
 
+mod error;
+pub use error::{PhyError, PhyOp};
+mod config;
+pub use config::*;
+mod pa;
+pub use pa::*;
+mod timing;
+pub use timing::*;
+mod cad;
+pub use cad::*;
+mod afc;
+pub use afc::*;
+mod timestamp;
+pub use timestamp::*;
+mod scan;
+pub use scan::*;
+mod ranging;
+pub use ranging::*;
+mod lrfhss;
+pub use lrfhss::*;
+mod syncword;
+pub use syncword::*;
+use fmt::{irq_flags, radio_mode_name};
+
+/// Step of the image calibration frequency (in Hz)
+const CALIB_STEP_HZ: u32 = 4_000_000;
+
+/// Maximum distance between a channel and a calibrated frequency (in Hz)
+const CALIB_RANGE_HZ: u32 = 8_000_000;
+
+/// Maximum number of frequencies calibrated at once
+const CALIB_MAX_FREQ: usize = 3;
+
+/// Distance between two calibration points of a band, keeping channels in between
+/// within range despite the rounding to a 4MHz step
+const CALIB_SPACING_HZ: u32 = 2 * CALIB_RANGE_HZ - CALIB_STEP_HZ;
+
+/// Bit of the calibration frequency selecting the HF RX path
+const CALIB_HF_PATH: u16 = 1 << 15;
+
+/// Maximum number of side detectors, demodulating other spreading factors in parallel
+pub const MAX_SIDE_DET: usize = 3;
+
+/// Detailed status of the last LoRa packet received
+/// The chip packet status (GetLoraPacketStatus) has no frequency error estimate:
+/// only CRC, coding rate, length, SNR, RSSI and detector are reported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoraRxStatus {
+    /// Average RSSI over the packet (in 0.5dBm steps, e.g. -201 for -100.5dBm)
+    pub rssi_half_db: i16,
+    /// Estimated RSSI of the LoRa signal itself, i.e. without noise (in 0.5dBm steps)
+    pub rssi_signal_half_db: i16,
+    /// Estimated SNR (in 0.25dB steps)
+    pub snr_quarter_db: i16,
+    /// Coding rate read from the explicit header (None if unknown)
+    pub coding_rate: Option<lora_phy::mod_params::CodingRate>,
+    /// CRC presence flag read from the explicit header
+    pub crc_on: bool,
+    /// Detector which received the packet: 0 for the main one, 1 to 3 for side detectors
+    /// (decoded from the one-hot flags reported by the chip, None if no flag is set)
+    pub detector: Option<u8>,
+    /// Spreading factor of the packet
+    pub spreading_factor: Option<SpreadingFactor>,
+}
+
+impl LoraRxStatus {
+    /// Packet RSSI in dBm
+    pub fn rssi_dbm(&self) -> i16 {
+        self.rssi_half_db / 2
+    }
+
+    /// Signal RSSI in dBm
+    pub fn rssi_signal_dbm(&self) -> i16 {
+        self.rssi_signal_half_db / 2
+    }
+
+    /// SNR rounded to the nearest dB
+    pub fn snr_db(&self) -> i16 {
+        (self.snr_quarter_db + 2) >> 2
+    }
+}
+
+/// Transmission test mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TxTestMode {
+    /// Unmodulated carrier
+    ContinuousWave,
+    /// Infinite preamble with the current modulation
+    InfinitePreamble,
+    /// Pseudo-random data (PRBS9) with the current modulation
+    Prbs9,
+}
+
+impl From<TxTestMode> for TestMode {
+    fn from(value: TxTestMode) -> Self {
+        match value {
+            TxTestMode::ContinuousWave => TestMode::Tone,
+            TxTestMode::InfinitePreamble => TestMode::Preamble,
+            TxTestMode::Prbs9 => TestMode::Prbs9,
+        }
+    }
+}
+
+/// Count of packets received with errors
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RxErrorStats {
+    pub crc: u32,
+    pub header: u32,
+}
+
 /// Wrapper around the Lr2021 Driver to implement the LoRaPhy traits
 /// This allows integration in lora-rs which provide a LoRaWAN stack implementation
 pub struct Lr2021LoraPhy<O, SPI, IRQ, M:BusyPin> {
     pub driver: Lr2021<O,SPI,M>,
     irq: IRQ,
-    dio_irq: DioNum
+    dio_irq: DioNum,
+    config: Lr2021LoraPhyConfig,
+    /// RF switch DIOs need to be (re-)configured
+    rf_switch_stale: bool,
+    /// DIOs removed from the RF switch configuration, to be released (one bit per DIO5 to DIO11)
+    rf_switch_released: u8,
+    /// Last channel frequency set
+    freq_hz: u32,
+    /// PA and output power (in 0.5dB steps) applied by the last TX power configuration
+    tx_power: Option<(Pa, i8)>,
+    /// Last modulation parameters applied
+    mod_params: Option<ModulationParams>,
+    /// Last packet parameters applied
+    pkt_params: Option<PacketParams>,
+    /// LoRa syncword (two 5-bit symbols) set during init
+    sync_word: (u8, u8),
+    /// Spreading factors of the side detectors
+    side_det: [Option<SpreadingFactor>; MAX_SIDE_DET],
+    /// Spreading factor of the last packet received
+    rx_sf: Option<SpreadingFactor>,
+    /// Automatic frequency correction
+    afc: Option<Afc>,
+    /// Delay between the TX command and the start of the transmission
+    tx_start_latency: Duration,
+    /// Force low data rate optimisation on/off instead of deriving it from the symbol time
+    ldro_override: Option<bool>,
+    /// Provide the ranging delay calibration for the modulation used
+    /// (None for the base delay of the driver)
+    ranging_delay_fn: Option<RangingDelayFn>,
+    /// Packet type set to ranging: the RF frequency must be patched after each change
+    ranging: bool,
+    /// Frequencies of the last image calibration (in 4MHz steps)
+    calib_freqs: [Option<u16>; CALIB_MAX_FREQ],
+    /// Keep payload of packet received with CRC error in the FIFO
+    keep_corrupted: bool,
+    rx_errors: RxErrorStats,
+    /// Provide CAD parameters for the modulation used
+    cad_params_fn: CadParamsFn,
+    /// Action at the end of the last CAD started
+    cad_exit: CadExit,
+    last_error: Option<PhyError>,
 }
 
 // Create driver with busy pin implementing wait
@@ -21,22 +176,780 @@ impl<I,O,SPI> Lr2021LoraPhy<O,SPI, I, BusyAsync<I>> where
 {
     /// Create a LR2021 Device with async busy pin
     pub fn new(nreset: O, busy: I, spi: SPI, nss: O, irq: I, dio_irq: DioNum) -> Self {
+        Self::new_with_config(nreset, busy, spi, nss, irq, dio_irq, Lr2021LoraPhyConfig::new())
+    }
+
+    /// Create a LR2021 Device with async busy pin and a board configuration
+    /// (TCXO, regulator, RF switch, ...) applied during `init_lora`
+    pub fn new_with_config(nreset: O, busy: I, spi: SPI, nss: O, irq: I, dio_irq: DioNum, config: Lr2021LoraPhyConfig) -> Self {
         Self {
             driver: Lr2021::new(nreset, busy, spi, nss),
-            irq, dio_irq
+            irq, dio_irq, config,
+            rf_switch_stale: true,
+            rf_switch_released: 0,
+            freq_hz: 0,
+            tx_power: None,
+            mod_params: None,
+            pkt_params: None,
+            sync_word: syncword_ext(SYNCWORD_PRIVATE),
+            side_det: [None; MAX_SIDE_DET],
+            rx_sf: None,
+            afc: None,
+            tx_start_latency: Duration::from_micros(0),
+            ldro_override: None,
+            ranging_delay_fn: None,
+            ranging: false,
+            calib_freqs: [None; CALIB_MAX_FREQ],
+            keep_corrupted: false,
+            rx_errors: RxErrorStats::default(),
+            cad_params_fn: CadParams::recommended,
+            cad_exit: CadExit::CadOnly,
+            last_error: None,
         }
     }
 }
 
+impl<O, SPI, IRQ, M:BusyPin> Lr2021LoraPhy<O,SPI,IRQ,M> {
+
+    /// Last error reported by the wrapper, with the operation which failed
+    /// and the underlying driver error
+    pub fn last_error(&self) -> Option<PhyError> {
+        self.last_error
+    }
+
+    /// Clear the last error
+    pub fn clear_last_error(&mut self) {
+        self.last_error = None;
+    }
+
+    /// PA and output power (in 0.5dB steps) applied by the last TX power configuration
+    pub fn tx_power(&self) -> Option<(Pa, i8)> {
+        self.tx_power
+    }
+
+    /// Number of packets received with CRC or header error
+    pub fn rx_errors(&self) -> RxErrorStats {
+        self.rx_errors
+    }
+
+    /// Reset the count of packets received with errors
+    pub fn clear_rx_errors(&mut self) {
+        self.rx_errors = RxErrorStats::default();
+    }
+
+    /// Keep the payload of packets received with a CRC error in the RX FIFO:
+    /// it can then be read with `get_rx_payload` for diagnostics, before the next `do_rx`.
+    /// When disabled (default) the FIFO is flushed so the next packet starts clean.
+    pub fn set_keep_corrupted_payload(&mut self, en: bool) {
+        self.keep_corrupted = en;
+    }
+
+    /// Override the CAD parameters used for each modulation
+    /// (default to `CadParams::recommended`)
+    pub fn set_cad_params_fn(&mut self, cad_params_fn: CadParamsFn) {
+        self.cad_params_fn = cad_params_fn;
+    }
+
+    /// Spreading factor of the last packet received (main or side detector)
+    /// Updated by `get_rx_packet_status`
+    pub fn rx_spreading_factor(&self) -> Option<SpreadingFactor> {
+        self.rx_sf
+    }
+
+    /// Enable automatic frequency correction: each frequency error provided with `update_afc`
+    /// updates a running offset applied on the next `set_channel`
+    pub fn enable_afc(&mut self, config: AfcConfig) {
+        self.afc = Some(Afc::new(config));
+    }
+
+    /// Update the frequency correction with the error measured on a received packet
+    /// against a stable reference (e.g. the offset reported by a gateway with a disciplined clock):
+    /// the LR2021 packet status provides no frequency error estimate.
+    /// Only packets of the main detector are used. Return the new offset, None if AFC is disabled
+    pub fn update_afc(&mut self, freq_error_hz: i32, rx_status: &LoraRxStatus) -> Option<i32> {
+        let afc = self.afc.as_mut()?;
+        if rx_status.detector == Some(0) {
+            let offset = afc.update(freq_error_hz, rx_status.snr_quarter_db);
+            trace!("update_afc: error={}Hz offset={}Hz", freq_error_hz, offset);
+        }
+        Some(afc.offset_hz())
+    }
+
+    /// Disable automatic frequency correction (applied on the next `set_channel`)
+    pub fn disable_afc(&mut self) {
+        self.afc = None;
+    }
+
+    /// Automatic frequency correction state
+    pub fn afc(&self) -> Option<&Afc> {
+        self.afc.as_ref()
+    }
+
+    /// Mutable access to the automatic frequency correction, e.g. to reset it
+    pub fn afc_mut(&mut self) -> Option<&mut Afc> {
+        self.afc.as_mut()
+    }
+
+    /// Delay between the TX command and the actual start of the transmission
+    /// (SPI transfer, PA ramp-up), used to advance scheduled transmissions
+    pub fn set_tx_start_latency(&mut self, latency: Duration) {
+        self.tx_start_latency = latency;
+    }
+
+    /// Override the ranging delay calibration used for each modulation
+    /// (default to the base delay provided by the driver)
+    pub fn set_ranging_delay_fn(&mut self, ranging_delay_fn: RangingDelayFn) {
+        self.ranging_delay_fn = Some(ranging_delay_fn);
+    }
+
+    /// Force low data rate optimisation on or off in `create_modulation_params`,
+    /// or derive it from the symbol duration (None, default)
+    pub fn set_ldro_override(&mut self, ldro: Option<bool>) {
+        self.ldro_override = ldro;
+    }
+
+    /// Time on air (in us) of a packet with a given payload length, using the last modulation
+    /// and packet parameters applied. Return None if they were not set yet
+    pub fn time_on_air_us(&self, payload_len: u8) -> Option<u32> {
+        let mdltn_params = self.mod_params?;
+        let mut pkt_params = self.pkt_params?;
+        pkt_params.payload_length = payload_len;
+        Some(time_on_air_us(&mdltn_params, &pkt_params))
+    }
+
+    /// Board configuration
+    pub fn config(&self) -> &Lr2021LoraPhyConfig {
+        &self.config
+    }
+
+    /// Change the board configuration: applied on next `init_lora`
+    /// The RF switch is updated on the next mode change: DIOs not used anymore are released
+    pub fn set_config(&mut self, config: Lr2021LoraPhyConfig) {
+        for (idx, (old, new)) in self.config.rf_switch.iter().zip(config.rf_switch.iter()).enumerate() {
+            if old.is_some() && new.is_none() {
+                self.rf_switch_released |= 1 << idx;
+            }
+        }
+        self.config = config;
+        self.rf_switch_stale = true;
+    }
+
+    /// Record a driver error and convert it to a lora-phy RadioError
+    fn fail(&mut self, op: PhyOp, err: Lr2021Error) -> RadioError {
+        let err = PhyError::Driver { op, err };
+        self.last_error = Some(err);
+        err.into()
+    }
+}
+
+impl<O, SPI, IRQ, M:BusyPin> Lr2021LoraPhy<O,SPI,IRQ,M>
+    where O: OutputPin, SPI: SpiBus<u8>, IRQ: InputPin + Wait, M:BusyPin
+{
+
+    /// Apply board configuration: regulator, clock source, RF switch and PA
+    async fn apply_config(&mut self) -> Result<(), RadioError> {
+        let config = self.config;
+        // Regulator can only be changed in Standby RC
+        self.driver.set_chip_mode(ChipMode::StandbyRc).await.map_err(|e| self.fail(PhyOp::SetChipMode, e))?;
+        let simo_en = config.regulator == Regulator::DcDc;
+        self.driver.set_regulator_mode(simo_en).await.map_err(|e| self.fail(PhyOp::SetRegulator, e))?;
+        if let Some(tcxo) = config.tcxo {
+            // Start time is expressed in step of the 32.768kHz RTC
+            let start_time = ((tcxo.startup_us as u64 * 32768).div_ceil(1_000_000)) as u32;
+            self.driver.set_tcxo(tcxo.voltage, start_time).await.map_err(|e| self.fail(PhyOp::SetTcxo, e))?;
+        }
+        if let Some(trim) = config.xosc_trim {
+            self.driver.set_xosc_trim(trim.xta, trim.xtb, trim.delay_us).await.map_err(|e| self.fail(PhyOp::SetXoscTrim, e))?;
+        }
+        self.apply_rf_switch().await
+    }
+
+    /// Configure the PA and output power for a transmission on a given frequency
+    /// The PA is selected according to the configuration (or the frequency),
+    /// the power is clamped to the PA range and the LF PA duty-cycle/slices are optimised for this power.
+    /// Return the power actually applied (in 0.5dB steps, e.g. 44 for 22dBm)
+    pub async fn set_tx_power(&mut self, output_power: i32, frequency_in_hz: u32, ramp: RampTime) -> Result<i8, RadioError> {
+        let pa = Pa::select(self.config.pa, frequency_in_hz);
+        let pwr = pa.clamp_power(output_power);
+        match pa.settings(pwr) {
+            Some(settings) => {
+                trace!("set_tx_power: pa={:?} req={}dBm applied={}/2dBm duty_cycle={} slices={}",
+                    pa, output_power, pwr, settings.duty_cycle, settings.slices);
+                self.driver.set_pa_lf(PaLfMode::LfPaFsm, settings.duty_cycle, settings.slices).await
+            }
+            None => {
+                trace!("set_tx_power: pa={:?} req={}dBm applied={}/2dBm", pa, output_power, pwr);
+                self.driver.set_pa_hf().await
+            }
+        }.map_err(|e| self.fail(PhyOp::SetPa, e))?;
+        self.driver.set_tx_params(pwr, ramp).await.map_err(|e| self.fail(PhyOp::SetTxParams, e))?;
+        self.tx_power = Some((pa, pwr));
+        Ok(pwr)
+    }
+
+    /// Start a CAD with a given action at the end of the detection
+    /// The timeout (in RTC steps) applies to the RX/TX started after the CAD
+    async fn start_cad(&mut self, mdltn_params: &ModulationParams, exit: CadExit, timeout: u32) -> Result<(), RadioError> {
+        self.ensure_rf_switch().await?;
+        self.configure_cad(mdltn_params, exit, timeout).await?;
+        self.driver.set_lora_cad().await.map_err(|e| self.fail(PhyOp::SetCad, e))
+    }
+
+    /// Set modulation and CAD parameters
+    async fn configure_cad(&mut self, mdltn_params: &ModulationParams, exit: CadExit, timeout: u32) -> Result<(), RadioError> {
+        self.set_modulation_params(mdltn_params).await?;
+        let cad = (self.cad_params_fn)(mdltn_params.spreading_factor, mdltn_params.bandwidth);
+        trace!("configure_cad: exit={:?} nb_symbols={} pbl_any={} pnr_delta={} det_peak={}",
+            exit, cad.nb_symbols, cad.pbl_any, cad.pnr_delta, cad.det_peak.unwrap_or(0));
+        self.driver.set_lora_cad_params(cad.nb_symbols, cad.pbl_any, cad.pnr_delta, exit.into(), timeout, cad.det_peak)
+            .await.map_err(|e| self.fail(PhyOp::SetCadParams, e))?;
+        self.cad_exit = exit;
+        Ok(())
+    }
+
+    /// Run a CAD on each channel for each spreading factor and report detections in `detections`:
+    /// return the number of detections (limited by the size of `detections`).
+    /// `detections` must hold at least one entry, otherwise `InvalidConfiguration` is returned.
+    /// Modulation and CAD parameters are configured once per spreading factor, only the frequency
+    /// changes between two CADs (each channel being image calibrated if needed).
+    /// The last modulation and channel set through lora-phy are restored at the end,
+    /// except with `CadScanMode::FirstThenRx` on a detection where the radio stays in reception
+    /// with the modulation of the detection.
+    pub async fn cad_scan(
+        &mut self,
+        channels: &[u32],
+        sfs: &[SpreadingFactor],
+        bandwidth: Bandwidth,
+        coding_rate: lora_phy::mod_params::CodingRate,
+        mode: CadScanMode,
+        detections: &mut [CadDetection],
+    ) -> Result<usize, RadioError> {
+        use lr2021::status::*;
+        if detections.is_empty() {
+            return Err(RadioError::InvalidConfiguration);
+        }
+        self.ensure_rf_switch().await?;
+        let intr = Intr::new(IRQ_MASK_LORA_TXRX|IRQ_MASK_CAD_DONE|IRQ_MASK_CAD_DETECTED);
+        self.driver.set_dio_irq(self.dio_irq, intr).await.map_err(|e| self.fail(PhyOp::SetDioIrq, e))?;
+        let prev_params = self.mod_params;
+        let mut nb_det = 0;
+        for sf in sfs {
+            let params = self.create_modulation_params(*sf, bandwidth, coding_rate, self.freq_hz)?;
+            let (exit, timeout) = match mode {
+                CadScanMode::FirstThenRx { rx_timeout_symbols } =>
+                    (CadExit::Rx, symbols_to_rtc_steps(rx_timeout_symbols, *sf, bandwidth)),
+                _ => (CadExit::CadOnly, 0),
+            };
+            self.configure_cad(&params, exit, timeout).await?;
+            for freq in channels {
+                self.ensure_calibrated(*freq).await?;
+                self.driver.set_rf(*freq).await.map_err(|e| self.fail(PhyOp::SetChannel, e))?;
+                self.clear_irq_status().await?;
+                self.driver.set_lora_cad().await.map_err(|e| self.fail(PhyOp::SetCad, e))?;
+                self.await_irq().await?;
+                let (_,intr) = self.driver.get_status().await.map_err(|e| self.fail(PhyOp::GetStatus, e))?;
+                if !(intr.cad_done() && intr.cad_detected()) {
+                    continue;
+                }
+                trace!("cad_scan: activity on {}Hz SF{}", freq, sf_value(*sf));
+                if let Some(det) = detections.get_mut(nb_det) {
+                    *det = CadDetection { freq_hz: *freq, spreading_factor: *sf };
+                    nb_det += 1;
+                }
+                match mode {
+                    CadScanMode::All if nb_det < detections.len() => {}
+                    // Chip is already in reception: IRQ for CAD are kept until cleared by the RX flow
+                    CadScanMode::FirstThenRx { .. } => {
+                        self.freq_hz = *freq;
+                        return Ok(nb_det);
+                    }
+                    _ => {
+                        self.end_cad_scan(prev_params).await?;
+                        return Ok(nb_det);
+                    }
+                }
+            }
+        }
+        self.end_cad_scan(prev_params).await?;
+        Ok(nb_det)
+    }
+
+    /// Clear CAD interrupts and restore the modulation and channel used before a CAD scan
+    async fn end_cad_scan(&mut self, prev_params: Option<ModulationParams>) -> Result<(), RadioError> {
+        self.clear_irq_status().await?;
+        if let Some(params) = prev_params {
+            self.set_modulation_params(&params).await?;
+        }
+        self.restore_channel().await
+    }
+
+    /// Listen-Before-Talk transmission: run a CAD and transmit the payload
+    /// already in the FIFO only if the channel is clear.
+    /// Completion is reported by `process_irq_event` with `RadioMode::Transmit`:
+    /// `Done` once transmitted or `PhyError::ChannelBusy` if activity was detected.
+    pub async fn do_lbt_tx(&mut self, mdltn_params: &ModulationParams) -> Result<(), RadioError> {
+        use lr2021::status::*;
+        let intr = Intr::new(IRQ_MASK_TX_DONE|IRQ_MASK_TIMEOUT|IRQ_MASK_CAD_DONE|IRQ_MASK_CAD_DETECTED);
+        self.driver.set_dio_irq(self.dio_irq, intr).await.map_err(|e| self.fail(PhyOp::SetDioIrq, e))?;
+        self.start_cad(mdltn_params, CadExit::Lbt, 0).await
+    }
+
+    /// Wake-on-activity: run a CAD and start a reception if activity was detected,
+    /// with a timeout expressed in symbols (0 for no timeout).
+    /// Completion is reported by `process_irq_event` with `RadioMode::Receive`:
+    /// `ReceiveTimeout` is returned when no activity was detected.
+    pub async fn do_cad_rx(&mut self, mdltn_params: &ModulationParams, rx_timeout_symbols: u16) -> Result<(), RadioError> {
+        use lr2021::status::*;
+        let intr = Intr::new(IRQ_MASK_LORA_TXRX|IRQ_MASK_CAD_DONE|IRQ_MASK_CAD_DETECTED);
+        self.driver.set_dio_irq(self.dio_irq, intr).await.map_err(|e| self.fail(PhyOp::SetDioIrq, e))?;
+        let timeout = symbols_to_rtc_steps(rx_timeout_symbols, mdltn_params.spreading_factor, mdltn_params.bandwidth);
+        self.start_cad(mdltn_params, CadExit::Rx, timeout).await
+    }
+
+    /// Demodulate up to `MAX_SIDE_DET` other spreading factors in parallel of the main one,
+    /// using the bandwidth, coding rate, IQ inversion and syncword of the main modulation.
+    /// Side detectors are (re-)applied on each `set_modulation_params` and when the IQ inversion
+    /// of the packet parameters changes: an empty list disables them.
+    /// A side detector on the spreading factor of the main modulation is skipped.
+    /// Return `InvalidConfiguration` for duplicated spreading factors or when the syncword
+    /// has no 1-byte equivalent (the only format supported for side detectors).
+    /// The spreading factor of a received packet is available with `rx_spreading_factor`.
+    pub async fn set_side_detectors(&mut self, sfs: &[SpreadingFactor]) -> Result<(), RadioError> {
+        // Each detector must use a spreading factor different from the others
+        if sfs.len() > MAX_SIDE_DET || sfs.iter().enumerate().any(|(i, sf)| sfs[..i].contains(sf)) {
+            return Err(RadioError::InvalidConfiguration);
+        }
+        if !sfs.is_empty() && syncword_from_ext(self.sync_word.0, self.sync_word.1).is_none() {
+            return Err(RadioError::InvalidConfiguration);
+        }
+        self.side_det = [None; MAX_SIDE_DET];
+        for (det, sf) in self.side_det.iter_mut().zip(sfs) {
+            *det = Some(*sf);
+        }
+        if self.mod_params.is_some() {
+            self.apply_side_detectors().await?;
+        }
+        Ok(())
+    }
+
+    /// Configure side detectors for the current modulation
+    async fn apply_side_detectors(&mut self) -> Result<(), RadioError> {
+        let Some(params) = self.mod_params else {
+            return Ok(());
+        };
+        let invert_iq = self.pkt_params.map(|p| p.iq_inverted).unwrap_or(false);
+        let mut cfg = [SidedetCfg::new(Sf::Sf5, Ldro::Off, false); MAX_SIDE_DET];
+        let mut nb_det = 0;
+        for sf in self.active_side_det(params.spreading_factor) {
+            let ldro = if ldro_required(sf, params.bandwidth) {Ldro::On} else {Ldro::Off};
+            cfg[nb_det] = SidedetCfg::new(to_sf(sf), ldro, invert_iq);
+            nb_det += 1;
+        }
+        trace!("apply_side_detectors: nb={}", nb_det);
+        self.driver.set_lora_sidedet_cfg(&cfg[..nb_det]).await.map_err(|e| self.fail(PhyOp::SetSideDet, e))?;
+        if nb_det > 0 {
+            match syncword_from_ext(self.sync_word.0, self.sync_word.1) {
+                Some(syncword) => self.driver.set_lora_sidedet_syncword(&[syncword; MAX_SIDE_DET][..nb_det]).await
+                    .map_err(|e| self.fail(PhyOp::SetSideDetSyncword, e))?,
+                None => warn!("apply_side_detectors: syncword {:?} not supported by side detectors", self.sync_word),
+            }
+        }
+        Ok(())
+    }
+
+    /// Spreading factors of the side detectors applied with a given main spreading factor
+    fn active_side_det(&self, main_sf: SpreadingFactor) -> impl Iterator<Item = SpreadingFactor> + '_ {
+        self.side_det.iter().flatten().copied().filter(move |sf| *sf != main_sf)
+    }
+
+    /// Read the detailed status of the last LoRa packet received
+    pub async fn get_lora_rx_status(&mut self) -> Result<LoraRxStatus, RadioError> {
+        let status = self.driver.get_lora_packet_status().await.map_err(|e| self.fail(PhyOp::GetPacketStatus, e))?;
+        // One flag per detector: bit 0 for the main one, bits 1 to 3 for the side detectors
+        let flags = status.detector();
+        let detector = (flags != 0).then(|| flags.trailing_zeros() as u8);
+        self.rx_sf = match detector {
+            Some(0) => self.mod_params.map(|p| p.spreading_factor),
+            Some(n) => self.mod_params.and_then(|p| self.active_side_det(p.spreading_factor).nth(n as usize - 1)),
+            None => None,
+        };
+        // Coding rate 5 to 7 correspond to the long interleaver variants
+        let coding_rate = match status.coding_rate() {
+            1 | 5 => Some(lora_phy::mod_params::CodingRate::_4_5),
+            2 | 6 => Some(lora_phy::mod_params::CodingRate::_4_6),
+            3     => Some(lora_phy::mod_params::CodingRate::_4_7),
+            4 | 7 => Some(lora_phy::mod_params::CodingRate::_4_8),
+            _ => None,
+        };
+        let rx_status = LoraRxStatus {
+            rssi_half_db: -(status.rssi_pkt() as i16),
+            rssi_signal_half_db: -(status.rssi_signal_pkt() as i16),
+            snr_quarter_db: status.snr_pkt() as i16,
+            coding_rate,
+            crc_on: status.crc(),
+            detector,
+            spreading_factor: self.rx_sf,
+        };
+        trace!("get_lora_rx_status: rssi={}/2dBm signal={}/2dBm snr={}/4dB cr={} crc={} detector={:?}",
+            rx_status.rssi_half_db, rx_status.rssi_signal_half_db, rx_status.snr_quarter_db,
+            status.coding_rate(), rx_status.crc_on, detector);
+        Ok(rx_status)
+    }
+
+    /// Latch the internal timer on TX done, RX done and LoRa header valid events:
+    /// the time of the last occurence of each event is then available with `get_timestamp`
+    pub async fn enable_timestamps(&mut self) -> Result<(), RadioError> {
+        for event in TimestampEvent::ALL {
+            self.driver.set_timestamp_source(event.index(), event.source()).await
+                .map_err(|e| self.fail(PhyOp::SetTimestampSource, e))?;
+        }
+        Ok(())
+    }
+
+    /// MCU time of the last occurence of an event, enabled by `enable_timestamps`
+    /// The chip provides the time elapsed since the event: it should be read
+    /// before the next event and within a few minutes (32-bit counter at 32MHz)
+    pub async fn get_timestamp(&mut self, event: TimestampEvent) -> Result<Instant, RadioError> {
+        let ticks = self.driver.get_timestamp(event.index()).await
+            .map_err(|e| self.fail(PhyOp::GetTimestamp, e))?;
+        let instant = ticks_to_instant(Instant::now(), ticks);
+        trace!("get_timestamp: event={:?} elapsed={} ticks", event, ticks);
+        Ok(instant)
+    }
+
+    /// Transmit the payload already in the FIFO at a given time
+    /// The chip waits in FS mode (PLL locked) until the executor timer expires.
+    /// The start time is only as precise as the embassy time base: lr2021 selects a 32.768kHz tick,
+    /// so the jitter is one tick (about 30.5us) plus the executor wake-up latency
+    /// and the variation of the SPI command time.
+    /// A time already in the past is reported with a warning and the transmission starts immediately.
+    /// Completion is reported through the normal IRQ path with `RadioMode::Transmit`.
+    pub async fn schedule_tx(&mut self, at: Instant) -> Result<(), RadioError> {
+        let now = Instant::now();
+        if at < now {
+            warn!("schedule_tx: start time already passed by {}us", (now - at).as_micros());
+        }
+        self.ensure_rf_switch().await?;
+        self.cad_exit = CadExit::CadOnly;
+        self.driver.set_chip_mode(ChipMode::Fs).await.map_err(|e| self.fail(PhyOp::SetChipMode, e))?;
+        let start = at.checked_sub(self.tx_start_latency).unwrap_or(at);
+        Timer::at(start).await;
+        self.driver.set_tx(0).await.map_err(|e| self.fail(PhyOp::SetTx, e))?;
+        trace!("schedule_tx: TX command completed {}us after start", Instant::now().saturating_duration_since(start).as_micros());
+        Ok(())
+    }
+
+    /// Transmit the payload already in the FIFO after a given delay
+    pub async fn schedule_tx_after(&mut self, delay: Duration) -> Result<(), RadioError> {
+        self.schedule_tx(Instant::now() + delay).await
+    }
+
+    /// Start a transmission test: runs until the chip mode is changed (e.g. `set_standby`)
+    pub async fn set_tx_test_mode(&mut self, mode: TxTestMode) -> Result<(), RadioError> {
+        trace!("set_tx_test_mode: mode={:?}", mode);
+        self.ensure_rf_switch().await?;
+        self.driver.set_tx_test(mode.into())
+            .await
+            .map_err(|e| self.fail(PhyOp::SetTxTest, e))
+    }
+
+    /// Run a transmission test for a given duration then go back to standby
+    pub async fn run_tx_test(&mut self, mode: TxTestMode, duration: Duration) -> Result<(), RadioError> {
+        self.set_tx_test_mode(mode).await?;
+        Timer::after(duration).await;
+        self.set_standby().await
+    }
+
+    /// Measure the RSSI on a list of frequencies (e.g. `(902_300_000..=914_900_000).step_by(200_000)`)
+    /// and fill one result per channel: return the number of channels scanned,
+    /// limited by the size of `results`.
+    /// Each channel is image calibrated first if not covered by the last calibration.
+    /// The radio is left in standby on the last channel set by `set_channel`.
+    pub async fn spectral_scan(&mut self, freqs: impl IntoIterator<Item = u32>, config: &ScanConfig, results: &mut [ChannelScan]) -> Result<usize, RadioError> {
+        self.ensure_rf_switch().await?;
+        let mut nb_chan = 0;
+        for (freq, result) in freqs.into_iter().zip(results.iter_mut()) {
+            // Image calibration is only run from standby, the chip is still in reception otherwise
+            if !self.is_calibrated(freq) {
+                self.set_standby().await?;
+                self.ensure_calibrated(freq).await?;
+            }
+            self.driver.set_rf(freq).await.map_err(|e| self.fail(PhyOp::SetChannel, e))?;
+            self.driver.set_rx(0xFFFFFFFF, true).await.map_err(|e| self.fail(PhyOp::SetRx, e))?;
+            *result = ChannelScan::new(freq);
+            for _ in 0..config.nb_samples {
+                Timer::after(config.sample_interval).await;
+                let rssi = self.driver.get_rssi_inst().await.map_err(|e| self.fail(PhyOp::GetRssi, e))?;
+                result.add_sample(-(rssi as i16), config);
+            }
+            trace!("spectral_scan: freq={}Hz rssi min={}/2 avg={}/2 max={}/2 dBm",
+                freq, result.rssi_min_half_db, result.rssi_avg_half_db, result.rssi_max_half_db);
+            nb_chan += 1;
+        }
+        self.set_standby().await?;
+        self.restore_channel().await?;
+        Ok(nb_chan)
+    }
+
+    /// Set back the frequency of the last `set_channel` after a scan
+    async fn restore_channel(&mut self) -> Result<(), RadioError> {
+        if self.freq_hz != 0 {
+            self.set_channel(self.freq_hz).await?;
+        }
+        Ok(())
+    }
+
+    /// Check if a frequency is close enough to the last image calibration
+    fn is_calibrated(&self, frequency_in_hz: u32) -> bool {
+        self.calib_freqs.iter().flatten()
+            .any(|step| ((*step & !CALIB_HF_PATH) as u32 * CALIB_STEP_HZ).abs_diff(frequency_in_hz) <= CALIB_RANGE_HZ)
+    }
+
+    /// Run image calibration on up to 3 frequencies and remember them
+    async fn calibrate_freqs(&mut self, freqs: &[u32]) -> Result<(), RadioError> {
+        let mut steps = [0u16; CALIB_MAX_FREQ];
+        let nb = freqs.len().min(CALIB_MAX_FREQ);
+        for (step, freq) in steps.iter_mut().zip(freqs) {
+            *step = calib_step(*freq);
+        }
+        trace!("calibrate_freqs: steps={:?}", &steps[..nb]);
+        self.driver.calib_fe(&steps[..nb]).await.map_err(|e| self.fail(PhyOp::CalibImage, e))?;
+        self.calib_freqs = [None; CALIB_MAX_FREQ];
+        for (cache, step) in self.calib_freqs.iter_mut().zip(&steps[..nb]) {
+            *cache = Some(*step);
+        }
+        Ok(())
+    }
+
+    /// Calibrate the image rejection for a channel if not already covered by the last calibration:
+    /// a channel inside a band of the configuration calibrates 3 points covering the band,
+    /// or the part of the band around the channel when the band is wider than 36MHz.
+    pub async fn ensure_calibrated(&mut self, frequency_in_hz: u32) -> Result<(), RadioError> {
+        if self.is_calibrated(frequency_in_hz) {
+            return Ok(());
+        }
+        let band = self.config.calib_bands.iter().flatten()
+            .find(|(min, max)| (*min..=*max).contains(&frequency_in_hz))
+            .copied();
+        match band {
+            Some((min, max)) => self.calibrate_freqs(&calib_points(min, max, frequency_in_hz)).await,
+            None => self.calibrate_freqs(&[frequency_in_hz]).await,
+        }
+    }
+
+    /// Switch to ranging (RTToF) with a given role, using the LoRa modulation
+    /// and packet parameters already set on the current channel.
+    /// Return `InvalidConfiguration` if they were not set or for bandwidths other than 125, 250 and 500kHz.
+    /// The exchange is then started with `do_tx` for an initiator or `do_rx` for a responder
+    /// and its completion awaited with `await_ranging`.
+    /// Call `init_lora` or `exit_ranging` to go back to standard LoRa packets.
+    pub async fn set_ranging(&mut self, role: RangingRole) -> Result<(), RadioError> {
+        use lr2021::status::*;
+        let params = self.mod_params.ok_or(RadioError::InvalidConfiguration)?;
+        let pkt_params = self.pkt_params.ok_or(RadioError::InvalidConfiguration)?;
+        if !ranging_supported(params.bandwidth) {
+            return Err(RadioError::InvalidConfiguration);
+        }
+        trace!("set_ranging: role={:?}", role);
+        self.driver.set_packet_type(PacketType::Ranging).await.map_err(|e| self.fail(PhyOp::SetRangingPacketType, e))?;
+        self.ranging = true;
+        // Modulation and packet parameters are not kept across a packet type change
+        let is_initiator = matches!(role, RangingRole::Initiator { .. });
+        self.driver.set_ranging_modulation(&to_lora_modulation(&params), is_initiator).await
+            .map_err(|e| self.fail(PhyOp::SetRangingModulation, e))?;
+        self.set_packet_params(&pkt_params).await?;
+        self.driver.patch_ranging_rf().await.map_err(|e| self.fail(PhyOp::PatchRangingRf, e))?;
+        self.driver.set_ranging_params(false, false, RANGING_NB_SYMBOLS).await
+            .map_err(|e| self.fail(PhyOp::SetRangingParams, e))?;
+        let irq = match role {
+            RangingRole::Initiator { target_addr } => {
+                self.driver.set_ranging_req_addr(target_addr).await.map_err(|e| self.fail(PhyOp::SetRangingAddr, e))?;
+                IRQ_MASK_RNG_EXCH_VLD|IRQ_MASK_RNG_TIMEOUT
+            }
+            RangingRole::Responder { addr, check_len } => {
+                self.driver.set_ranging_dev_addr(addr, Some(check_len)).await.map_err(|e| self.fail(PhyOp::SetRangingAddr, e))?;
+                IRQ_MASK_RNG_RESP_DONE|IRQ_MASK_RNG_REQ_DIS|IRQ_MASK_TIMEOUT
+            }
+        };
+        let delay = match self.ranging_delay_fn {
+            Some(delay_fn) => delay_fn(params.spreading_factor, params.bandwidth),
+            None => self.driver.get_ranging_base_delay(&to_lora_modulation(&params)),
+        };
+        self.driver.set_ranging_txrx_delay(delay).await.map_err(|e| self.fail(PhyOp::SetRangingDelay, e))?;
+        self.driver.set_dio_irq(self.dio_irq, Intr::new(irq)).await.map_err(|e| self.fail(PhyOp::SetDioIrq, e))
+    }
+
+    /// Go back to standard LoRa packets after ranging
+    pub async fn exit_ranging(&mut self) -> Result<(), RadioError> {
+        self.restore_lora().await
+    }
+
+    /// Set the LoRa syncword (e.g. `SYNCWORD_PUBLIC` or `SYNCWORD_PRIVATE`)
+    /// Side detectors are updated to use the same syncword
+    pub async fn set_syncword(&mut self, syncword: u8) -> Result<(), RadioError> {
+        let (s1, s2) = syncword_ext(syncword);
+        self.set_syncword_ext(s1, s2).await
+    }
+
+    /// Set the LoRa syncword from its two 5-bit symbols (e.g. (6,8) for public networks)
+    /// Side detectors are updated to use the same syncword: when they are enabled,
+    /// `InvalidConfiguration` is returned for a syncword without 1-byte equivalent.
+    pub async fn set_syncword_ext(&mut self, s1: u8, s2: u8) -> Result<(), RadioError> {
+        if self.side_det.iter().any(Option::is_some) && syncword_from_ext(s1 & 0x1F, s2 & 0x1F).is_none() {
+            return Err(RadioError::InvalidConfiguration);
+        }
+        self.driver.set_lora_syncword_ext(s1, s2).await.map_err(|e| self.fail(PhyOp::SetSyncword, e))?;
+        self.sync_word = (s1 & 0x1F, s2 & 0x1F);
+        if self.side_det.iter().any(Option::is_some) {
+            self.apply_side_detectors().await?;
+        }
+        Ok(())
+    }
+
+    /// Set the LoRa syncword from the 16-bit value used by SX126x (e.g. 0x3444 for public networks)
+    pub async fn set_syncword_sx126x(&mut self, reg: u16) -> Result<(), RadioError> {
+        let (s1, s2) = syncword_from_sx126x(reg);
+        self.set_syncword_ext(s1, s2).await
+    }
+
+    /// Current LoRa syncword, as two 5-bit symbols
+    pub fn syncword(&self) -> (u8, u8) {
+        self.sync_word
+    }
+
+    /// Switch back to LoRa packets with the last syncword, modulation and packet parameters
+    async fn restore_lora(&mut self) -> Result<(), RadioError> {
+        self.driver.set_packet_type(PacketType::Lora).await.map_err(|e| self.fail(PhyOp::InitPacketType, e))?;
+        self.ranging = false;
+        self.driver.set_lora_syncword_ext(self.sync_word.0, self.sync_word.1).await.map_err(|e| self.fail(PhyOp::InitSyncword, e))?;
+        if let Some(params) = self.mod_params {
+            self.set_modulation_params(&params).await?;
+        }
+        if let Some(params) = self.pkt_params {
+            self.set_packet_params(&params).await?;
+        }
+        Ok(())
+    }
+
+    /// Transmit a payload with LR-FHSS on the current channel and wait for the end of transmission.
+    /// The hopping sequence is derived from `seed` (e.g. a random value from the LoRaWAN stack).
+    /// The radio is switched back to LoRa afterwards, with the previous parameters.
+    pub async fn lrfhss_tx(&mut self, params: &LrFhssParams, seed: u32, payload: &[u8]) -> Result<(), RadioError> {
+        use lr2021::status::*;
+        let hop_seq = params.hop_sequence(seed);
+        trace!("lrfhss_tx: params={:?} hop_seq={} len={}", params, hop_seq, payload.len());
+        self.ensure_rf_switch().await?;
+        self.driver.set_packet_type(PacketType::LrFhss).await.map_err(|e| self.fail(PhyOp::SetLrFhssPacketType, e))?;
+        self.driver.set_lrfhss_syncword(LRFHSS_SYNCWORD).await.map_err(|e| self.fail(PhyOp::SetLrFhssSyncword, e))?;
+        // Frame (headers and fragments) is built by the chip directly into the TX FIFO
+        self.driver.lrfhss_build_packet(
+            params.header_count, params.cr, params.grid, params.hopping_mode(),
+            params.bw as u8, hop_seq, params.device_offset, payload
+        ).await.map_err(|e| self.fail(PhyOp::BuildLrFhssFrame, e))?;
+        self.driver.set_dio_irq(self.dio_irq, Intr::new(IRQ_MASK_TX_DONE|IRQ_MASK_TIMEOUT)).await
+            .map_err(|e| self.fail(PhyOp::SetDioIrq, e))?;
+        self.clear_irq_status().await?;
+        self.driver.set_tx(0).await.map_err(|e| self.fail(PhyOp::SetTx, e))?;
+        let res = self.await_irq().await;
+        let status = self.driver.get_status().await.map_err(|e| self.fail(PhyOp::GetStatus, e));
+        self.clear_irq_status().await?;
+        self.restore_lora().await?;
+        res?;
+        let (_,intr) = status?;
+        if intr.tx_done() {Ok(())} else {Err(RadioError::TransmitTimeout)}
+    }
+
+    /// Wait for the end of a ranging exchange.
+    /// For an initiator, return the measured distance,
+    /// for a responder return None once the response was sent.
+    /// `ReceiveTimeout` is reported if no response/request was received.
+    pub async fn await_ranging(&mut self) -> Result<Option<RangingResult>, RadioError> {
+        loop {
+            self.await_irq().await?;
+            let (_,intr) = self.driver.get_status().await.map_err(|e| self.fail(PhyOp::GetStatus, e))?;
+            self.clear_irq_status().await?;
+            if intr.rng_exch_vld() {
+                return self.get_ranging_result().await.map(Some);
+            }
+            if intr.rng_resp_done() {
+                return Ok(None);
+            }
+            if intr.rng_timeout() || intr.timeout() {
+                return Err(RadioError::ReceiveTimeout);
+            }
+            // Request for another address: responder keeps listening
+            trace!("await_ranging: ignored irq={}", irq_flags(intr));
+        }
+    }
+
+    /// Read the result of the last ranging exchange (initiator only)
+    pub async fn get_ranging_result(&mut self) -> Result<RangingResult, RadioError> {
+        let params = self.mod_params.ok_or(RadioError::InvalidConfiguration)?;
+        let rsp = self.driver.get_ranging_result().await.map_err(|e| self.fail(PhyOp::GetRangingResult, e))?;
+        let raw = rsp.rng();
+        let result = RangingResult {
+            raw,
+            distance_m: ranging_distance_m(raw, params.bandwidth),
+            rssi_dbm: -((rsp.rssi() >> 1) as i16),
+        };
+        trace!("get_ranging_result: raw={} distance={}m", raw, result.distance_m);
+        Ok(result)
+    }
+
+    /// Configure DIOs controlling the RF switch: the chip then drives them
+    /// according to the active path (standby, RX/TX on LF/HF).
+    /// DIOs removed from the configuration are released, except the one used for IRQ.
+    async fn apply_rf_switch(&mut self) -> Result<(), RadioError> {
+        let rf_switch = self.config.rf_switch;
+        // The IRQ DIO would be turned into an RF switch control
+        if rf_switch[rf_switch_idx(self.dio_irq)].is_some() {
+            warn!("apply_rf_switch: IRQ DIO used by the RF switch");
+            return Err(RadioError::InvalidConfiguration);
+        }
+        for (idx, (dio, levels)) in RF_SWITCH_DIOS.iter().zip(rf_switch.iter()).enumerate() {
+            let Some(levels) = levels else {
+                if self.rf_switch_released & (1 << idx) != 0 && *dio != self.dio_irq {
+                    self.driver.set_dio_function(*dio, DioFunc::None, PullDrive::PullNone).await
+                        .map_err(|e| self.fail(PhyOp::SetDioFunction, e))?;
+                }
+                continue;
+            };
+            self.driver.set_dio_function(*dio, DioFunc::RfSwitch, PullDrive::PullNone).await
+                .map_err(|e| self.fail(PhyOp::SetDioFunction, e))?;
+            self.driver.set_dio_rf_switch(*dio, levels.tx_hf, levels.rx_hf, levels.tx_lf, levels.rx_lf, levels.standby).await
+                .map_err(|e| self.fail(PhyOp::SetRfSwitch, e))?;
+        }
+        self.rf_switch_stale = false;
+        self.rf_switch_released = 0;
+        Ok(())
+    }
+
+    /// Re-configure RF switch DIOs if they were lost (deep sleep) or changed
+    async fn ensure_rf_switch(&mut self) -> Result<(), RadioError> {
+        if self.rf_switch_stale {
+            trace!("RF switch re-configured");
+            self.apply_rf_switch().await?;
+        }
+        Ok(())
+    }
+}
+
 impl<O, SPI, IRQ, M:BusyPin> RadioKind for Lr2021LoraPhy<O,SPI,IRQ,M>
     where O: OutputPin, SPI: SpiBus<u8>, IRQ: InputPin + Wait, M:BusyPin
 {
 
-    // LoRa Init: Run Calibration, SetPacketType and Syncword
+    // LoRa Init: Apply board configuration, run Calibration, SetPacketType and Syncword
     async fn init_lora(&mut self, sync_word: u8) -> Result<(), RadioError> {
-        self.driver.calib_fe(&[]).await.map_err(|_| RadioError::OpError(0))?;
-        self.driver.set_packet_type(PacketType::Lora).await.map_err(|_| RadioError::OpError(1))?;
-        self.driver.set_lora_syncword(sync_word).await.map_err(|_| RadioError::OpError(2))
+        let (s1, s2) = self.config.syncword.unwrap_or(syncword_ext(sync_word));
+        trace!("init_lora: sync_word=({},{})", s1, s2);
+        self.apply_config().await?;
+        self.driver.calib_fe(&[]).await.map_err(|e| self.fail(PhyOp::InitCalib, e))?;
+        self.calib_freqs = [None; CALIB_MAX_FREQ];
+        self.driver.set_packet_type(PacketType::Lora).await.map_err(|e| self.fail(PhyOp::InitPacketType, e))?;
+        self.ranging = false;
+        self.driver.set_lora_syncword_ext(s1, s2).await.map_err(|e| self.fail(PhyOp::InitSyncword, e))?;
+        self.sync_word = (s1 & 0x1F, s2 & 0x1F);
+        Ok(())
     }
 
     fn create_modulation_params(
@@ -46,11 +959,9 @@ impl<O, SPI, IRQ, M:BusyPin> RadioKind for Lr2021LoraPhy<O,SPI,IRQ,M>
         coding_rate: lora_phy::mod_params::CodingRate,
         frequency_in_hz: u32,
     ) -> Result<ModulationParams, RadioError> {
-        let ldro_en = match bandwidth {
-            Bandwidth::_125KHz => spreading_factor == SpreadingFactor::_11 || spreading_factor == SpreadingFactor::_12,
-            Bandwidth::_250KHz => spreading_factor == SpreadingFactor::_12,
-            _ => false
-        };
+        trace!("create_modulation_params: sf={} bw={}Hz cr=4/{} freq={}Hz",
+            sf_value(spreading_factor), bw_hz(bandwidth), cr_denom(coding_rate), frequency_in_hz);
+        let ldro_en = self.ldro_override.unwrap_or_else(|| ldro_required(spreading_factor, bandwidth));
         let low_data_rate_optimize = ldro_en as u8;
         Ok(ModulationParams {
             spreading_factor,
@@ -70,6 +981,8 @@ impl<O, SPI, IRQ, M:BusyPin> RadioKind for Lr2021LoraPhy<O,SPI,IRQ,M>
         iq_inverted: bool,
         modulation_params: &ModulationParams,
     ) -> Result<PacketParams, RadioError> {
+        trace!("create_packet_params: preamble={} implicit={} len={} crc={} iq_inv={}",
+            preamble_length, implicit_header, payload_length, crc_on, iq_inverted);
         if ((modulation_params.spreading_factor == SpreadingFactor::_5)
             || (modulation_params.spreading_factor == SpreadingFactor::_6))
             && (preamble_length < 12)
@@ -87,82 +1000,83 @@ impl<O, SPI, IRQ, M:BusyPin> RadioKind for Lr2021LoraPhy<O,SPI,IRQ,M>
     }
 
     async fn reset(&mut self, _delay: &mut impl lora_phy::DelayNs) -> Result<(), RadioError> {
-        self.driver.reset().await.map_err(|_| RadioError::Reset)
+        trace!("reset");
+        self.driver.reset().await.map_err(|e| self.fail(PhyOp::Reset, e))?;
+        // DIO configuration and calibration are lost on reset
+        self.rf_switch_stale = true;
+        self.rf_switch_released = 0;
+        self.calib_freqs = [None; CALIB_MAX_FREQ];
+        self.ranging = false;
+        Ok(())
     }
 
     async fn ensure_ready(&mut self, mode: RadioMode) -> Result<(), RadioError> {
+        trace!("ensure_ready: mode={}", radio_mode_name(mode));
         match mode {
             RadioMode::Sleep => {
-                self.driver.wake_up().await.map_err(|_| RadioError::DIO1)
+                self.driver.wake_up().await.map_err(|e| self.fail(PhyOp::WakeUp, e))
             }
-            _ => self.driver.wait_ready(Duration::from_nanos(0)).await.map_err(|_| RadioError::DIO1)
+            _ => self.driver.wait_ready(Duration::from_nanos(0)).await.map_err(|e| self.fail(PhyOp::WaitReady, e))
         }
     }
 
     async fn set_standby(&mut self) -> Result<(), RadioError> {
+        trace!("set_standby: chip_mode=StandbyXosc");
+        self.ensure_rf_switch().await?;
         self.driver.set_chip_mode(ChipMode::StandbyXosc)
             .await
-            .map_err(|_| RadioError::SPI)
+            .map_err(|e| self.fail(PhyOp::SetChipMode, e))
     }
 
     async fn set_sleep(&mut self, warm_start_if_possible: bool, _delay: &mut impl lora_phy::DelayNs) -> Result<(), RadioError> {
         let chip_mode = if warm_start_if_possible {ChipMode::DeepRetention} else {ChipMode::DeepSleep};
+        trace!("set_sleep: chip_mode={}", if warm_start_if_possible {"DeepRetention"} else {"DeepSleep"});
         self.driver.set_chip_mode(chip_mode)
             .await
-            .map_err(|_| RadioError::SPI)
+            .map_err(|e| self.fail(PhyOp::SetChipMode, e))?;
+        // DIO configuration and calibration are not retained in deep sleep
+        if !warm_start_if_possible {
+            self.rf_switch_stale = true;
+            self.calib_freqs = [None; CALIB_MAX_FREQ];
+        }
+        Ok(())
     }
 
     // Tx/Rx buffer are implemented as a FIFO -> nothing to do
     async fn set_tx_rx_buffer_base_address( &mut self, _tx_base_addr: usize, _rx_base_addr: usize,) -> Result<(), RadioError> {
+        trace!("set_tx_rx_buffer_base_address");
         Ok(())
     }
 
     async fn set_tx_power_and_ramp_time(
         &mut self,
         output_power: i32,
-        _mdltn_params: Option<&ModulationParams>,
+        mdltn_params: Option<&ModulationParams>,
         is_tx_prep: bool,
     ) -> Result<(), RadioError> {
+        trace!("set_tx_power_and_ramp_time: req={}dBm tx_prep={}", output_power, is_tx_prep);
         let ramp = if is_tx_prep {RampTime::Ramp32u} else {RampTime::Ramp128u};
-        let pwr = output_power.clamp(-9, 22) as i8;
-        self.driver.set_tx_params(pwr, ramp).await.map_err(|_| RadioError::InvalidConfiguration)
+        let freq = mdltn_params.map(|p| p.frequency_in_hz).unwrap_or(self.freq_hz);
+        self.set_tx_power(output_power, freq, ramp).await.map(|_| ())
     }
 
     async fn set_modulation_params(&mut self, mdltn_params: &ModulationParams) -> Result<(), RadioError> {
-        let sf = match mdltn_params.spreading_factor {
-            SpreadingFactor::_5  => Sf::Sf5,
-            SpreadingFactor::_6  => Sf::Sf6,
-            SpreadingFactor::_7  => Sf::Sf7,
-            SpreadingFactor::_8  => Sf::Sf8,
-            SpreadingFactor::_9  => Sf::Sf9,
-            SpreadingFactor::_10 => Sf::Sf10,
-            SpreadingFactor::_11 => Sf::Sf11,
-            SpreadingFactor::_12 => Sf::Sf12,
-        };
-        let bw = match mdltn_params.bandwidth {
-            Bandwidth::_7KHz => LoraBw::Bw7,
-            Bandwidth::_10KHz => LoraBw::Bw10,
-            Bandwidth::_15KHz => LoraBw::Bw15,
-            Bandwidth::_20KHz => LoraBw::Bw20,
-            Bandwidth::_31KHz => LoraBw::Bw31,
-            Bandwidth::_41KHz => LoraBw::Bw41,
-            Bandwidth::_62KHz => LoraBw::Bw62,
-            Bandwidth::_125KHz => LoraBw::Bw125,
-            Bandwidth::_250KHz => LoraBw::Bw250,
-            Bandwidth::_500KHz => LoraBw::Bw500,
-        };
-        let cr = match mdltn_params.coding_rate {
-            lora_phy::mod_params::CodingRate::_4_5 => LoraCr::Cr1Ham45Si,
-            lora_phy::mod_params::CodingRate::_4_6 => LoraCr::Cr2Ham23Si,
-            lora_phy::mod_params::CodingRate::_4_7 => LoraCr::Cr3Ham47Si,
-            lora_phy::mod_params::CodingRate::_4_8 => LoraCr::Cr4Ham12Si,
-        };
-        let ldro = if mdltn_params.low_data_rate_optimize!=0 {Ldro::On} else {Ldro::Off};
-        let modulation = LoraModulationParams {sf,bw,cr,ldro};
-        self.driver.set_lora_modulation(&modulation).await.map_err(|_| RadioError::InvalidConfiguration)
+        trace!("set_modulation_params: sf={} bw={}Hz cr=4/{} ldro={}",
+            sf_value(mdltn_params.spreading_factor), bw_hz(mdltn_params.bandwidth),
+            cr_denom(mdltn_params.coding_rate), mdltn_params.low_data_rate_optimize);
+        let modulation = to_lora_modulation(mdltn_params);
+        self.driver.set_lora_modulation(&modulation).await.map_err(|e| self.fail(PhyOp::SetModulation, e))?;
+        self.mod_params = Some(*mdltn_params);
+        if self.side_det.iter().any(Option::is_some) {
+            self.apply_side_detectors().await?;
+        }
+        Ok(())
     }
 
     async fn set_packet_params(&mut self, pkt_params: &PacketParams) -> Result<(), RadioError> {
+        trace!("set_packet_params: preamble={} implicit={} len={} crc={} iq_inv={}",
+            pkt_params.preamble_length, pkt_params.implicit_header, pkt_params.payload_length,
+            pkt_params.crc_on, pkt_params.iq_inverted);
         let header_type = if pkt_params.implicit_header {HeaderType::Implicit} else {HeaderType::Explicit};
         let params = LoraPacketParams {
             pbl_len: pkt_params.preamble_length,
@@ -171,78 +1085,115 @@ impl<O, SPI, IRQ, M:BusyPin> RadioKind for Lr2021LoraPhy<O,SPI,IRQ,M>
             crc_en: pkt_params.crc_on,
             invert_iq: pkt_params.iq_inverted
         };
-        self.driver.set_lora_packet(&params).await.map_err(|_| RadioError::InvalidConfiguration)
+        self.driver.set_lora_packet(&params).await.map_err(|e| self.fail(PhyOp::SetPacket, e))?;
+        let iq_changed = self.pkt_params.map(|p| p.iq_inverted) != Some(pkt_params.iq_inverted);
+        self.pkt_params = Some(*pkt_params);
+        // Side detectors use the IQ inversion of the packet, set after the modulation by lora-phy
+        if iq_changed && self.side_det.iter().any(Option::is_some) {
+            self.apply_side_detectors().await?;
+        }
+        Ok(())
     }
 
     async fn calibrate_image(&mut self, frequency_in_hz: u32) -> Result<(), RadioError> {
-        // Calibration is done on a freqency multiple of 4MHz
-        // Approximate by a right shift of 22 bits i.e. 4.194MHz
-        let freq_4m = (frequency_in_hz >> 22) as u16;
-        self.driver.calib_fe(&[freq_4m]).await.map_err(|_| RadioError::OpError(3))
+        trace!("calibrate_image: freq={}Hz", frequency_in_hz);
+        self.ensure_calibrated(frequency_in_hz).await
     }
 
     async fn set_channel(&mut self, frequency_in_hz: u32) -> Result<(), RadioError> {
-        self.driver.set_rf(frequency_in_hz).await.map_err(|_| RadioError::OpError(4))
+        let rf_freq = self.afc.map(|afc| afc.apply(frequency_in_hz)).unwrap_or(frequency_in_hz);
+        trace!("set_channel: freq={}Hz rf={}Hz", frequency_in_hz, rf_freq);
+        self.ensure_calibrated(rf_freq).await?;
+        self.driver.set_rf(rf_freq).await.map_err(|e| self.fail(PhyOp::SetChannel, e))?;
+        if self.ranging {
+            self.driver.patch_ranging_rf().await.map_err(|e| self.fail(PhyOp::PatchRangingRf, e))?;
+        }
+        self.freq_hz = frequency_in_hz;
+        Ok(())
     }
 
     async fn set_payload(&mut self, payload: &[u8]) -> Result<(), RadioError> {
-        self.driver.wr_tx_fifo_from(payload).await.map_err(|_| RadioError::OpError(5))
+        trace!("set_payload: len={}", payload.len());
+        self.driver.wr_tx_fifo_from(payload).await.map_err(|e| self.fail(PhyOp::SetPayload, e))
     }
 
     async fn do_tx(&mut self) -> Result<(), RadioError> {
-        self.driver.set_tx(0).await.map_err(|_| RadioError::OpError(6))
+        trace!("do_tx");
+        self.ensure_rf_switch().await?;
+        self.cad_exit = CadExit::CadOnly;
+        self.driver.set_tx(0).await.map_err(|e| self.fail(PhyOp::SetTx, e))
     }
 
     async fn do_rx(&mut self, rx_mode: lora_phy::RxMode) -> Result<(), RadioError> {
+        trace!("do_rx: mode={}", radio_mode_name(RadioMode::Receive(rx_mode)));
+        self.ensure_rf_switch().await?;
+        self.cad_exit = CadExit::CadOnly;
+        // Drop any leftover (e.g. corrupted payload kept for diagnostics) before a new reception
+        self.driver.clear_rx_fifo().await.map_err(|e| self.fail(PhyOp::ClearRxFifo, e))?;
         if let RxMode::DutyCycle(params) = rx_mode {
             // Setting DRAM1-3 retention to 0: should only be needed if a patch RAM is set and none are required at the moment ...
             self.driver.set_rx_duty_cycle(params.rx_time, params.sleep_time, false, 0)
-                .await.map_err(|_| RadioError::OpError(7))
+                .await.map_err(|e| self.fail(PhyOp::SetRx, e))
         } else {
-            let timeout = if let RxMode::Single(timeout) = rx_mode {timeout as u32} else {0xFFFFFFFF};
+            let timeout = match rx_mode {
+                // lora-phy expresses the timeout in symbols while the chip counts in RTC steps
+                RxMode::Single(nb_symbols) => {
+                    let params = self.mod_params.ok_or(RadioError::InvalidConfiguration)?;
+                    let timeout = symbols_to_rtc_steps(nb_symbols, params.spreading_factor, params.bandwidth);
+                    trace!("do_rx: timeout={} symbols -> {} RTC steps", nb_symbols, timeout);
+                    timeout
+                }
+                _ => 0xFFFFFFFF,
+            };
             self.driver.set_rx(timeout, true)
-                .await.map_err(|_| RadioError::OpError(7))
+                .await.map_err(|e| self.fail(PhyOp::SetRx, e))
         }
     }
 
-    async fn get_rx_payload(&mut self, _params: &PacketParams, rx_buffer: &mut [u8]) -> Result<u8, RadioError> {
-        let pkt_len = self.driver.get_rx_pkt_len().await.map_err(|_| RadioError::OpError(8))? as usize;
-        match self.driver.rd_rx_fifo_to(rx_buffer).await {
-            Ok(_) => Ok(pkt_len as u8), // Should be OK: Lora packets are < 256
-            Err(_) => Err(RadioError::OpError(9)),
+    async fn get_rx_payload(&mut self, params: &PacketParams, rx_buffer: &mut [u8]) -> Result<u8, RadioError> {
+        let rx_len = self.driver.get_rx_pkt_len().await.map_err(|e| self.fail(PhyOp::GetRxPktLen, e))? as usize;
+        // Implicit header packets always have the configured length
+        let pkt_len = if params.implicit_header {params.payload_length as usize} else {rx_len};
+        trace!("get_rx_payload: pkt_len={} rx_len={} buffer_len={}", pkt_len, rx_len, rx_buffer.len());
+        if pkt_len > u8::MAX as usize || pkt_len > rx_buffer.len() {
+            // Flush the packet so that it does not end up in front of the next one
+            self.driver.clear_rx_fifo().await.map_err(|e| self.fail(PhyOp::ClearRxFifo, e))?;
+            return if pkt_len > u8::MAX as usize {
+                Err(RadioError::PayloadSizeUnexpected(pkt_len))
+            } else {
+                Err(RadioError::PayloadSizeMismatch(pkt_len, rx_buffer.len()))
+            };
         }
+        self.driver.rd_rx_fifo_to(&mut rx_buffer[..pkt_len]).await.map_err(|e| self.fail(PhyOp::ReadRxFifo, e))?;
+        Ok(pkt_len as u8)
     }
 
     async fn get_rx_packet_status(&mut self) -> Result<PacketStatus, RadioError> {
-        let status = self.driver.get_lora_packet_status().await.map_err(|_| RadioError::OpError(10))?;
-        let rssi_db = -((status.rssi_pkt()>>1) as i16);
-        let snr_db = ((status.snr_pkt() + 2) >> 2 ) as i16;
+        let status = self.get_lora_rx_status().await?;
         Ok(PacketStatus {
-            rssi: rssi_db,
-            snr: snr_db,
+            rssi: status.rssi_dbm(),
+            snr: status.snr_db(),
         })
     }
 
     async fn do_cad(&mut self, mdltn_params: &ModulationParams) -> Result<(), RadioError> {
-        self.set_modulation_params(mdltn_params).await?;
-        self.driver.set_lora_cad_params(4, false, 9, ExitMode::CadOnly, 0, None)
-            .await.map_err(|_| RadioError::OpError(11))?;
-        self.driver.set_lora_cad().await.map_err(|_| RadioError::OpError(12))
+        self.start_cad(mdltn_params, CadExit::CadOnly, 0).await
     }
 
     async fn set_tx_continuous_wave_mode(&mut self) -> Result<(), RadioError> {
-        self.driver.set_tx_test(lr2021::radio::TestMode::Tone)
-            .await
-            .map_err(|_| RadioError::OpError(12))
+        trace!("set_tx_continuous_wave_mode");
+        self.set_tx_test_mode(TxTestMode::ContinuousWave).await
     }
 
     async fn get_rssi(&mut self) -> Result<i16, RadioError> {
-        let rssi = self.driver.get_rssi_inst().await.map_err(|_| RadioError::OpError(13))?;
+        let rssi = self.driver.get_rssi_inst().await.map_err(|e| self.fail(PhyOp::GetRssi, e))?;
         let rssi_db = -((rssi>>1) as i16);
+        trace!("get_rssi: rssi={}dBm", rssi_db);
         Ok(rssi_db)
     }
 
     async fn set_irq_params(&mut self, radio_mode: Option<lora_phy::mod_params::RadioMode>) -> Result<(), RadioError> {
+        trace!("set_irq_params: mode={}", radio_mode.map(radio_mode_name).unwrap_or("None"));
         use lr2021::status::*;
         let intr = match radio_mode {
             Some(RadioMode::Standby)  => Intr::new(IRQ_MASK_LORA_TXRX),
@@ -251,12 +1202,16 @@ impl<O, SPI, IRQ, M:BusyPin> RadioKind for Lr2021LoraPhy<O,SPI,IRQ,M>
             Some(RadioMode::ChannelActivityDetection) => Intr::new(IRQ_MASK_CAD_DONE|IRQ_MASK_CAD_DETECTED),
             _ => Intr::new(0),
         };
-        self.driver.set_dio_irq(self.dio_irq, intr).await.map_err(|_| RadioError::OpError(16))
+        self.driver.set_dio_irq(self.dio_irq, intr).await.map_err(|e| self.fail(PhyOp::SetDioIrq, e))
     }
 
     async fn await_irq(&mut self) -> Result<(), RadioError> {
+        trace!("await_irq");
         self.irq.wait_for_rising_edge().await
-            .map_err(|_| RadioError::Irq)
+            .map_err(|_| {
+                self.last_error = Some(PhyError::IrqPin);
+                RadioError::Irq
+            })
     }
 
     async fn get_irq_state(
@@ -264,30 +1219,62 @@ impl<O, SPI, IRQ, M:BusyPin> RadioKind for Lr2021LoraPhy<O,SPI,IRQ,M>
         radio_mode: RadioMode,
         cad_activity_detected: Option<&mut bool>,
     ) -> Result<Option<IrqState>, RadioError> {
-        let (_,intr) = self.driver.get_status().await.map_err(|_| RadioError::OpError(15))?;
+        let (_,intr) = self.driver.get_status().await.map_err(|e| self.fail(PhyOp::GetStatus, e))?;
+        trace!("get_irq_state: mode={} irq={}", radio_mode_name(radio_mode), irq_flags(intr));
         if intr.timeout() {
-            return Err(RadioError::TransmitTimeout);
+            match radio_mode {
+                RadioMode::Transmit => return Err(RadioError::TransmitTimeout),
+                RadioMode::Receive(_) => return Err(RadioError::ReceiveTimeout),
+                // CAD only stops on timeout when no activity was detected
+                RadioMode::ChannelActivityDetection if !intr.cad_done() => {
+                    if let Some(detected) = cad_activity_detected {
+                        *detected = false;
+                    }
+                    return Ok(Some(IrqState::Done));
+                }
+                _ => {}
+            }
         }
-        // Add debug message like SX126x impl ?
         let irq_state = match radio_mode {
             RadioMode::Transmit => {
                 if intr.tx_done() {Some(IrqState::Done)}
+                else if self.cad_exit == CadExit::Lbt && intr.cad_done() && intr.cad_detected() {
+                    debug!("get_irq_state: LBT channel busy");
+                    self.last_error = Some(PhyError::ChannelBusy);
+                    return Err(PhyError::ChannelBusy.into());
+                }
                 else {None}
             },
             RadioMode::Receive(_) => {
-                if intr.header_err() || intr.crc_error() {None}
+                if self.cad_exit == CadExit::Rx && intr.cad_done() && !intr.cad_detected() {
+                    debug!("get_irq_state: no activity detected by CAD");
+                    return Err(RadioError::ReceiveTimeout);
+                }
+                if intr.header_err() {
+                    self.rx_errors.header += 1;
+                    debug!("get_irq_state: header error");
+                    self.driver.clear_rx_fifo().await.map_err(|e| self.fail(PhyOp::ClearRxFifo, e))?;
+                    self.last_error = Some(PhyError::Header);
+                    return Err(PhyError::Header.into());
+                }
+                else if intr.crc_error() {
+                    self.rx_errors.crc += 1;
+                    debug!("get_irq_state: CRC error");
+                    if !self.keep_corrupted {
+                        self.driver.clear_rx_fifo().await.map_err(|e| self.fail(PhyOp::ClearRxFifo, e))?;
+                    }
+                    self.last_error = Some(PhyError::Crc);
+                    return Err(PhyError::Crc.into());
+                }
                 else if intr.rx_done() {Some(IrqState::Done)}
                 else if intr.preamble_detected() || intr.header_valid() {Some(IrqState::PreambleReceived)}
                 else {None}
             },
-            RadioMode::ChannelActivityDetection => {
-                if intr.cad_done() {
-                    if let Some(detected) = cad_activity_detected {
-                        *detected = intr.cad_detected();
-                    }
-                    Some(IrqState::Done)
+            RadioMode::ChannelActivityDetection if intr.cad_done() => {
+                if let Some(detected) = cad_activity_detected {
+                    *detected = intr.cad_detected();
                 }
-                else {None}
+                Some(IrqState::Done)
             },
             _ => {None},
         };
@@ -295,7 +1282,8 @@ impl<O, SPI, IRQ, M:BusyPin> RadioKind for Lr2021LoraPhy<O,SPI,IRQ,M>
     }
 
     async fn clear_irq_status(&mut self) -> Result<(), RadioError> {
-        self.driver.clear_irqs(Intr::new(0xFFFFFFFF)).await.map_err(|_| RadioError::OpError(14))
+        trace!("clear_irq_status");
+        self.driver.clear_irqs(Intr::new(0xFFFFFFFF)).await.map_err(|e| self.fail(PhyOp::ClearIrq, e))
     }
 
     // Process IRQ: just get and clear, no workaround to handle on LR2021
@@ -305,10 +1293,136 @@ impl<O, SPI, IRQ, M:BusyPin> RadioKind for Lr2021LoraPhy<O,SPI,IRQ,M>
         cad_activity_detected: Option<&mut bool>,
         clear_interrupts: bool,
     ) -> Result<Option<lora_phy::mod_traits::IrqState>, RadioError> {
+        trace!("process_irq_event: mode={} clear={}", radio_mode_name(radio_mode), clear_interrupts);
         let irq_state = self.get_irq_state(radio_mode,cad_activity_detected).await;
         if clear_interrupts {
             self.clear_irq_status().await?;
         }
         irq_state
     }
-}
\ No newline at end of file
+}
+
+/// Convert lora-phy spreading factor to LR2021 one
+fn to_sf(sf: SpreadingFactor) -> Sf {
+    match sf {
+        SpreadingFactor::_5  => Sf::Sf5,
+        SpreadingFactor::_6  => Sf::Sf6,
+        SpreadingFactor::_7  => Sf::Sf7,
+        SpreadingFactor::_8  => Sf::Sf8,
+        SpreadingFactor::_9  => Sf::Sf9,
+        SpreadingFactor::_10 => Sf::Sf10,
+        SpreadingFactor::_11 => Sf::Sf11,
+        SpreadingFactor::_12 => Sf::Sf12,
+    }
+}
+
+/// Convert lora-phy modulation parameters to LR2021 ones
+fn to_lora_modulation(mdltn_params: &ModulationParams) -> LoraModulationParams {
+    let sf = to_sf(mdltn_params.spreading_factor);
+    let bw = match mdltn_params.bandwidth {
+        Bandwidth::_7KHz => LoraBw::Bw7,
+        Bandwidth::_10KHz => LoraBw::Bw10,
+        Bandwidth::_15KHz => LoraBw::Bw15,
+        Bandwidth::_20KHz => LoraBw::Bw20,
+        Bandwidth::_31KHz => LoraBw::Bw31,
+        Bandwidth::_41KHz => LoraBw::Bw41,
+        Bandwidth::_62KHz => LoraBw::Bw62,
+        Bandwidth::_125KHz => LoraBw::Bw125,
+        Bandwidth::_250KHz => LoraBw::Bw250,
+        Bandwidth::_500KHz => LoraBw::Bw500,
+    };
+    let cr = match mdltn_params.coding_rate {
+        lora_phy::mod_params::CodingRate::_4_5 => LoraCr::Cr1Ham45Si,
+        lora_phy::mod_params::CodingRate::_4_6 => LoraCr::Cr2Ham23Si,
+        lora_phy::mod_params::CodingRate::_4_7 => LoraCr::Cr3Ham47Si,
+        lora_phy::mod_params::CodingRate::_4_8 => LoraCr::Cr4Ham12Si,
+    };
+    let ldro = if mdltn_params.low_data_rate_optimize!=0 {Ldro::On} else {Ldro::Off};
+    LoraModulationParams {sf,bw,cr,ldro}
+}
+
+/// Image calibration frequency (in 4MHz steps) closest to a frequency,
+/// with the MSB set when the channel uses the HF RX path
+fn calib_step(frequency_in_hz: u32) -> u16 {
+    let step = ((frequency_in_hz + CALIB_STEP_HZ / 2) / CALIB_STEP_HZ) as u16;
+    if Pa::for_frequency(frequency_in_hz) == Pa::Hf {step | CALIB_HF_PATH} else {step}
+}
+
+/// Calibration frequencies covering a band, centered on the band when it fits
+/// or on the channel (kept inside the band) when it does not.
+/// Points are kept inside the band: a narrow band is calibrated at its edges and center.
+fn calib_points(min: u32, max: u32, frequency_in_hz: u32) -> [u32; CALIB_MAX_FREQ] {
+    let window = CALIB_SPACING_HZ * CALIB_MAX_FREQ as u32;
+    let start = if max - min <= window {
+        (min + (max - min) / 2).saturating_sub(window / 2)
+    } else {
+        frequency_in_hz.saturating_sub(window / 2).clamp(min, max - window)
+    };
+    core::array::from_fn(|i| (start + CALIB_SPACING_HZ / 2 + i as u32 * CALIB_SPACING_HZ).clamp(min, max))
+}
+
+/// Spreading factor as a number
+fn sf_value(sf: SpreadingFactor) -> u8 {
+    match sf {
+        SpreadingFactor::_5  => 5,
+        SpreadingFactor::_6  => 6,
+        SpreadingFactor::_7  => 7,
+        SpreadingFactor::_8  => 8,
+        SpreadingFactor::_9  => 9,
+        SpreadingFactor::_10 => 10,
+        SpreadingFactor::_11 => 11,
+        SpreadingFactor::_12 => 12,
+    }
+}
+
+/// Bandwidth in Hz
+fn bw_hz(bw: Bandwidth) -> u32 {
+    match bw {
+        Bandwidth::_7KHz   =>   7_812,
+        Bandwidth::_10KHz  =>  10_417,
+        Bandwidth::_15KHz  =>  15_625,
+        Bandwidth::_20KHz  =>  20_833,
+        Bandwidth::_31KHz  =>  31_250,
+        Bandwidth::_41KHz  =>  41_667,
+        Bandwidth::_62KHz  =>  62_500,
+        Bandwidth::_125KHz => 125_000,
+        Bandwidth::_250KHz => 250_000,
+        Bandwidth::_500KHz => 500_000,
+    }
+}
+
+/// Coding rate denominator (4/x)
+fn cr_denom(cr: lora_phy::mod_params::CodingRate) -> u8 {
+    match cr {
+        lora_phy::mod_params::CodingRate::_4_5 => 5,
+        lora_phy::mod_params::CodingRate::_4_6 => 6,
+        lora_phy::mod_params::CodingRate::_4_7 => 7,
+        lora_phy::mod_params::CodingRate::_4_8 => 8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calib_step_path() {
+        assert_eq!(calib_step(868_000_000), 217);
+        assert_eq!(calib_step(869_900_000), 217);
+        assert_eq!(calib_step(870_000_000), 218);
+        assert_eq!(calib_step(2_440_000_000), 610 | CALIB_HF_PATH);
+    }
+
+    #[test]
+    fn calib_points_cover_band() {
+        for (min, max) in [(863_000_000, 870_000_000), (902_000_000, 928_000_000), (2_400_000_000, 2_500_000_000)] {
+            for freq in (min..=max).step_by(200_000) {
+                let points = calib_points(min, max, freq);
+                assert!(points.iter().any(|p| ((calib_step(*p) & !CALIB_HF_PATH) as u32 * CALIB_STEP_HZ).abs_diff(freq) <= CALIB_RANGE_HZ),
+                    "{freq}Hz not covered by {points:?}");
+            }
+        }
+        assert_eq!(calib_points(902_000_000, 928_000_000, 915_000_000), [903_000_000, 915_000_000, 927_000_000]);
+        assert_eq!(calib_points(863_000_000, 870_000_000, 868_100_000), [863_000_000, 866_500_000, 870_000_000]);
+    }
+}
@@ -0,0 +1,82 @@
+use lr2021::lrfhss::Hopping;
+pub use lr2021::lrfhss::{Grid, LrfhssCr};
+
+/// LR-FHSS occupied bandwidth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LrFhssBw {
+    Bw39k   = 0,
+    Bw85k   = 1,
+    Bw136k  = 2,
+    Bw183k  = 3,
+    Bw335k  = 4,
+    Bw386k  = 5,
+    Bw722k  = 6,
+    Bw773k  = 7,
+    Bw1523k = 8,
+    Bw1574k = 9,
+}
+
+/// Default LR-FHSS syncword used by LoRaWAN
+pub const LRFHSS_SYNCWORD: u32 = 0x2C0F7995;
+
+/// LR-FHSS transmission parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LrFhssParams {
+    pub cr: LrfhssCr,
+    pub grid: Grid,
+    pub bw: LrFhssBw,
+    /// Number of header replicas (2 to 4)
+    pub header_count: u8,
+    /// Enable frequency hopping (disable only for test)
+    pub hopping: bool,
+    /// Offset of the device inside the grid (in grid steps)
+    pub device_offset: i8,
+}
+
+impl LrFhssParams {
+    /// Default parameters for a coding rate, grid and bandwidth
+    /// Header count follows LoRaWAN: 3 for CR 1/3, 2 otherwise
+    pub fn new(cr: LrfhssCr, grid: Grid, bw: LrFhssBw) -> Self {
+        let header_count = if cr == LrfhssCr::Cr1p3 {3} else {2};
+        Self { cr, grid, bw, header_count, hopping: true, device_offset: 0 }
+    }
+
+    /// Parameters of the LR-FHSS data rates of the EU868 region (DR8 to DR11)
+    pub fn eu868(dr: u8) -> Option<Self> {
+        let (cr, bw) = match dr {
+            8  => (LrfhssCr::Cr1p3, LrFhssBw::Bw136k),
+            9  => (LrfhssCr::Cr2p3, LrFhssBw::Bw136k),
+            10 => (LrfhssCr::Cr1p3, LrFhssBw::Bw335k),
+            11 => (LrfhssCr::Cr2p3, LrFhssBw::Bw335k),
+            _ => return None,
+        };
+        Some(Self::new(cr, Grid::Grid4, bw))
+    }
+
+    /// Parameters of the LR-FHSS data rates of the US915 region (DR5 and DR6)
+    pub fn us915(dr: u8) -> Option<Self> {
+        let cr = match dr {
+            5 => LrfhssCr::Cr1p3,
+            6 => LrfhssCr::Cr2p3,
+            _ => return None,
+        };
+        Some(Self::new(cr, Grid::Grid25, LrFhssBw::Bw1523k))
+    }
+
+    /// Number of hopping sequences available
+    pub fn hop_sequence_count(&self) -> u16 {
+        if self.grid == Grid::Grid25 || self.bw < LrFhssBw::Bw335k {384} else {512}
+    }
+
+    /// Hopping mode of the chip
+    pub fn hopping_mode(&self) -> Hopping {
+        if self.hopping {Hopping::Hopping} else {Hopping::NoHopping}
+    }
+
+    /// Hopping sequence selected from a seed (e.g. random value drawn by the LoRaWAN stack)
+    pub fn hop_sequence(&self, seed: u32) -> u16 {
+        (seed % self.hop_sequence_count() as u32) as u16
+    }
+}
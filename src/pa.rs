@@ -0,0 +1,73 @@
+use crate::PaSelect;
+pub use lr2021::radio::RampTime;
+
+/// Frequency above which the high-frequency PA is used (in Hz)
+pub const HF_THRESHOLD_HZ: u32 = 1_500_000_000;
+
+/// Power amplifier used for a transmission
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Pa {
+    /// Low-frequency PA (sub-GHz)
+    Lf,
+    /// High-frequency PA (2.4GHz)
+    Hf,
+}
+
+/// LF PA duty-cycle and number of slices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PaSettings {
+    pub duty_cycle: u8,
+    pub slices: u8,
+}
+
+/// Minimum power (in 0.5dB steps), PA duty-cycle and slices for the LF PA (highest power first)
+const LF_PA_TABLE: [(i8, PaSettings); 5] = [
+    (40, PaSettings { duty_cycle: 6, slices: 7 }),
+    (34, PaSettings { duty_cycle: 5, slices: 5 }),
+    (28, PaSettings { duty_cycle: 4, slices: 3 }),
+    (20, PaSettings { duty_cycle: 3, slices: 2 }),
+    (i8::MIN, PaSettings { duty_cycle: 2, slices: 1 }),
+];
+
+impl Pa {
+    /// PA able to transmit on a given frequency
+    pub fn for_frequency(frequency_in_hz: u32) -> Self {
+        if frequency_in_hz >= HF_THRESHOLD_HZ {Pa::Hf} else {Pa::Lf}
+    }
+
+    /// PA used for a given selection and frequency
+    pub fn select(sel: PaSelect, frequency_in_hz: u32) -> Self {
+        match sel {
+            PaSelect::Auto => Self::for_frequency(frequency_in_hz),
+            PaSelect::Lf => Pa::Lf,
+            PaSelect::Hf => Pa::Hf,
+        }
+    }
+
+    /// Minimum and maximum output power (in 0.5dB steps, as expected by `set_tx_params`)
+    pub const fn power_range(&self) -> (i8, i8) {
+        match self {
+            Pa::Lf => (-19, 44),
+            Pa::Hf => (-39, 24),
+        }
+    }
+
+    /// Convert a requested power (in dBm) to 0.5dB steps, clamped to the PA range
+    pub fn clamp_power(&self, output_power: i32) -> i8 {
+        let (min, max) = self.power_range();
+        output_power.saturating_mul(2).clamp(min as i32, max as i32) as i8
+    }
+
+    /// LF PA duty-cycle and slices giving the best efficiency for an output power (in 0.5dB steps)
+    /// The HF PA has no such settings: None is returned
+    pub fn settings(&self, output_power: i8) -> Option<PaSettings> {
+        match self {
+            Pa::Lf => LF_PA_TABLE.iter()
+                .find(|(min, _)| output_power >= *min)
+                .map(|(_, s)| *s),
+            Pa::Hf => None,
+        }
+    }
+}
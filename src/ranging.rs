@@ -0,0 +1,71 @@
+use lora_phy::mod_params::{Bandwidth, SpreadingFactor};
+pub use lr2021::lora::CheckLength;
+
+use crate::bw_hz;
+
+/// Number of symbols of a ranging exchange (8 to 16, 12 being close to optimal)
+pub const RANGING_NB_SYMBOLS: u8 = 12;
+
+/// Role of the device in a ranging exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RangingRole {
+    /// Send a ranging request to a responder and measure the round-trip time
+    Initiator {
+        /// Address of the responder
+        target_addr: u32,
+    },
+    /// Answer ranging requests matching its address
+    Responder {
+        /// Address of the device
+        addr: u32,
+        /// Number of address bytes checked (starting from the LSB)
+        check_len: CheckLength,
+    },
+}
+
+/// Result of a ranging exchange
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RangingResult {
+    /// Raw round-trip measurement (signed 24 bits)
+    pub raw: i32,
+    /// Distance (in m)
+    pub distance_m: f32,
+    /// RSSI of the response (in dBm)
+    pub rssi_dbm: i16,
+}
+
+/// Function providing the ranging TX/RX delay calibration (in 32MHz clock cycles) for a given modulation
+pub type RangingDelayFn = fn(SpreadingFactor, Bandwidth) -> u32;
+
+/// Ranging is only supported on 125, 250 and 500kHz
+pub fn ranging_supported(bw: Bandwidth) -> bool {
+    matches!(bw, Bandwidth::_125KHz | Bandwidth::_250KHz | Bandwidth::_500KHz)
+}
+
+/// Convert a raw ranging result into a distance in meters
+pub fn ranging_distance_m(raw: i32, bw: Bandwidth) -> f32 {
+    // Distance = raw * 150 / (2^12 * BW[MHz])
+    (raw as f32) * 150_000_000.0 / (4096.0 * bw_hz(bw) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        // 2^12 units at 125kHz is 150/0.125 = 1200m
+        assert!((ranging_distance_m(4096, Bandwidth::_125KHz) - 1200.0).abs() < 1e-3);
+        assert!((ranging_distance_m(4096, Bandwidth::_500KHz) - 300.0).abs() < 1e-3);
+        assert!((ranging_distance_m(-1024, Bandwidth::_250KHz) + 150.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn supported_bandwidth() {
+        assert!(ranging_supported(Bandwidth::_125KHz));
+        assert!(ranging_supported(Bandwidth::_500KHz));
+        assert!(!ranging_supported(Bandwidth::_62KHz));
+    }
+}
@@ -0,0 +1,100 @@
+use embassy_time::Duration;
+
+/// Number of bins in the RSSI histogram of a spectral scan
+pub const SCAN_HIST_BINS: usize = 16;
+
+/// Spectral scan settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanConfig {
+    /// Number of RSSI measurements on each channel
+    pub nb_samples: u16,
+    /// Time between two measurements
+    pub sample_interval: Duration,
+    /// Lower bound of the first histogram bin (in dBm): lower values are counted in the first bin
+    pub hist_min_dbm: i16,
+    /// Width of a histogram bin (in dB): higher values are counted in the last bin
+    pub hist_step_db: u8,
+}
+
+impl Default for ScanConfig {
+    /// 100 samples every ms, histogram from -135dBm to -55dBm by 5dB
+    fn default() -> Self {
+        Self {
+            nb_samples: 100,
+            sample_interval: Duration::from_millis(1),
+            hist_min_dbm: -135,
+            hist_step_db: 5,
+        }
+    }
+}
+
+/// Result of the spectral scan on one channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChannelScan {
+    pub freq_hz: u32,
+    /// Minimum RSSI (in 0.5dBm steps)
+    pub rssi_min_half_db: i16,
+    /// Maximum RSSI (in 0.5dBm steps)
+    pub rssi_max_half_db: i16,
+    /// Average RSSI (in 0.5dBm steps)
+    pub rssi_avg_half_db: i16,
+    /// Number of samples in each RSSI bin
+    pub histogram: [u16; SCAN_HIST_BINS],
+    rssi_sum: i32,
+    nb_samples: u16,
+}
+
+impl ChannelScan {
+    pub fn new(freq_hz: u32) -> Self {
+        Self {
+            freq_hz,
+            rssi_min_half_db: i16::MAX,
+            rssi_max_half_db: i16::MIN,
+            rssi_avg_half_db: 0,
+            histogram: [0; SCAN_HIST_BINS],
+            rssi_sum: 0,
+            nb_samples: 0,
+        }
+    }
+
+    /// Number of samples measured
+    pub fn nb_samples(&self) -> u16 {
+        self.nb_samples
+    }
+
+    /// Add an RSSI measurement (in 0.5dBm steps)
+    pub fn add_sample(&mut self, rssi_half_db: i16, config: &ScanConfig) {
+        self.rssi_min_half_db = self.rssi_min_half_db.min(rssi_half_db);
+        self.rssi_max_half_db = self.rssi_max_half_db.max(rssi_half_db);
+        self.rssi_sum += rssi_half_db as i32;
+        self.nb_samples += 1;
+        self.rssi_avg_half_db = (self.rssi_sum / self.nb_samples as i32) as i16;
+        let step = 2 * config.hist_step_db.max(1) as i32;
+        let bin = (rssi_half_db as i32 - 2 * config.hist_min_dbm as i32).div_euclid(step);
+        self.histogram[bin.clamp(0, SCAN_HIST_BINS as i32 - 1) as usize] += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_sample() {
+        let config = ScanConfig::default();
+        let mut scan = ChannelScan::new(868_100_000);
+        // -135dBm, -125dBm, -140dBm (below the histogram) and 0dBm (above)
+        for rssi in [-270, -250, -280, 0] {
+            scan.add_sample(rssi, &config);
+        }
+        assert_eq!(scan.nb_samples(), 4);
+        assert_eq!(scan.rssi_min_half_db, -280);
+        assert_eq!(scan.rssi_max_half_db, 0);
+        assert_eq!(scan.rssi_avg_half_db, -200);
+        assert_eq!(scan.histogram[0], 2);
+        assert_eq!(scan.histogram[2], 1);
+        assert_eq!(scan.histogram[SCAN_HIST_BINS - 1], 1);
+        assert_eq!(scan.histogram.iter().sum::<u16>(), 4);
+    }
+}
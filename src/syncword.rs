@@ -0,0 +1,64 @@
+/// LoRa syncword of public networks (LoRaWAN)
+pub const SYNCWORD_PUBLIC: u8 = 0x34;
+
+/// LoRa syncword of private networks
+pub const SYNCWORD_PRIVATE: u8 = 0x12;
+
+/// Convert a 1-byte LoRa syncword (e.g. 0x34) into the two 5-bit symbols used by the LR2021 (e.g. (6,8))
+/// Each nibble of the syncword selects one symbol.
+pub fn syncword_ext(syncword: u8) -> (u8, u8) {
+    ((syncword >> 4) << 1, (syncword & 0x0F) << 1)
+}
+
+/// Convert the two 5-bit symbols used by the LR2021 (e.g. (6,8)) into a 1-byte LoRa syncword (e.g. 0x34)
+/// None if a symbol is odd: such syncwords have no 1-byte equivalent.
+pub fn syncword_from_ext(s1: u8, s2: u8) -> Option<u8> {
+    if s1 & 1 != 0 || s2 & 1 != 0 || s1 > 0x1F || s2 > 0x1F {
+        return None;
+    }
+    Some(((s1 >> 1) << 4) | (s2 >> 1))
+}
+
+/// Convert an SX126x syncword register value (e.g. 0x3444) into the two 5-bit symbols
+/// used by the LR2021 (e.g. (6,8))
+/// Each byte of the register holds one symbol in its 5 MSB, the 3 LSB being ignored.
+pub fn syncword_from_sx126x(reg: u16) -> (u8, u8) {
+    ((reg >> 11) as u8, ((reg >> 3) & 0x1F) as u8)
+}
+
+/// Convert an LR2021 syncword (e.g. 0x12) into the SX126x register value (e.g. 0x1424)
+pub fn syncword_to_sx126x(syncword: u8) -> u16 {
+    let syncword = syncword as u16;
+    ((syncword & 0xF0) << 8) | ((syncword & 0x0F) << 4) | 0x0404
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sx126x_reference() {
+        assert_eq!(syncword_from_sx126x(0x3444), (6, 8));
+        assert_eq!(syncword_from_sx126x(0x1424), (2, 4));
+        assert_eq!(syncword_to_sx126x(SYNCWORD_PUBLIC), 0x3444);
+        assert_eq!(syncword_to_sx126x(SYNCWORD_PRIVATE), 0x1424);
+        assert_eq!(syncword_ext(SYNCWORD_PUBLIC), (6, 8));
+        assert_eq!(syncword_ext(SYNCWORD_PRIVATE), (2, 4));
+    }
+
+    #[test]
+    fn ext_roundtrip() {
+        for syncword in 0..=u8::MAX {
+            let (s1, s2) = syncword_ext(syncword);
+            assert_eq!(syncword_from_ext(s1, s2), Some(syncword));
+        }
+        assert_eq!(syncword_from_ext(7, 8), None);
+    }
+
+    #[test]
+    fn sx126x_roundtrip() {
+        for syncword in 0..=u8::MAX {
+            assert_eq!(syncword_from_sx126x(syncword_to_sx126x(syncword)), syncword_ext(syncword));
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use embassy_time::{Duration, Instant};
+use lr2021::radio::{TimestampIndex, TimestampSource};
+
+/// Radio event latched by the LR2021 timestamp unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimestampEvent {
+    /// End of a transmission
+    TxDone,
+    /// End of a reception
+    RxDone,
+    /// Valid LoRa header received
+    HeaderValid,
+}
+
+impl TimestampEvent {
+    pub(crate) const ALL: [TimestampEvent; 3] = [
+        TimestampEvent::TxDone, TimestampEvent::RxDone, TimestampEvent::HeaderValid
+    ];
+
+    /// Timestamp slot used for this event
+    pub(crate) fn index(&self) -> TimestampIndex {
+        match self {
+            TimestampEvent::TxDone      => TimestampIndex::Ts0,
+            TimestampEvent::RxDone      => TimestampIndex::Ts1,
+            TimestampEvent::HeaderValid => TimestampIndex::Ts2,
+        }
+    }
+
+    pub(crate) fn source(&self) -> TimestampSource {
+        match self {
+            TimestampEvent::TxDone      => TimestampSource::TxDone,
+            TimestampEvent::RxDone      => TimestampSource::RxDone,
+            TimestampEvent::HeaderValid => TimestampSource::Header,
+        }
+    }
+}
+
+/// Convert a number of 32MHz clock ticks elapsed since an event into
+/// the MCU time of this event, given the time the value was read
+pub fn ticks_to_instant(read_at: Instant, elapsed_ticks: u32) -> Instant {
+    // One tick is 31.25ns
+    let elapsed = Duration::from_nanos((elapsed_ticks as u64 * 125) / 4);
+    read_at.checked_sub(elapsed).unwrap_or(Instant::MIN)
+}
@@ -0,0 +1,119 @@
+use lora_phy::mod_params::{Bandwidth, ModulationParams, PacketParams, SpreadingFactor};
+
+use crate::{bw_hz, cr_denom, sf_value};
+
+/// Frequency of the RTC used by the LR2021 timers (in Hz)
+pub const RTC_FREQ_HZ: u64 = 32768;
+
+/// Maximum timeout value in RTC step (24 bits, 0xFFFFFF is reserved for continuous mode)
+pub const RTC_TIMEOUT_MAX: u32 = 0xFFFFFE;
+
+/// Duration of a LoRa symbol (in ns)
+pub fn symbol_time_ns(sf: SpreadingFactor, bw: Bandwidth) -> u64 {
+    ((1u64 << sf_value(sf)) * 1_000_000_000).div_ceil(bw_hz(bw) as u64)
+}
+
+/// Convert a number of LoRa symbols into a timeout in RTC steps (rounded up)
+pub fn symbols_to_rtc_steps(nb_symbols: u16, sf: SpreadingFactor, bw: Bandwidth) -> u32 {
+    let steps = ((nb_symbols as u64) << sf_value(sf)) * RTC_FREQ_HZ;
+    let steps = steps.div_ceil(bw_hz(bw) as u64);
+    steps.min(RTC_TIMEOUT_MAX as u64) as u32
+}
+
+/// Symbol duration above which low data rate optimisation is required (in ns)
+pub const LDRO_SYMBOL_TIME_NS: u64 = 16_000_000;
+
+/// Low data rate optimisation required for a spreading factor and bandwidth,
+/// i.e. when the symbol lasts more than 16ms
+pub fn ldro_required(sf: SpreadingFactor, bw: Bandwidth) -> bool {
+    symbol_time_ns(sf, bw) > LDRO_SYMBOL_TIME_NS
+}
+
+/// Bandwidth expressed as a divider of 500kHz, to keep timing computation exact
+fn bw_div(bw: Bandwidth) -> u64 {
+    match bw {
+        Bandwidth::_7KHz   => 64,
+        Bandwidth::_10KHz  => 48,
+        Bandwidth::_15KHz  => 32,
+        Bandwidth::_20KHz  => 24,
+        Bandwidth::_31KHz  => 16,
+        Bandwidth::_41KHz  => 12,
+        Bandwidth::_62KHz  =>  8,
+        Bandwidth::_125KHz =>  4,
+        Bandwidth::_250KHz =>  2,
+        Bandwidth::_500KHz =>  1,
+    }
+}
+
+/// Time on air of a LoRa packet (in us, rounded up)
+/// The payload length is taken from the packet parameters
+pub fn time_on_air_us(mdltn_params: &ModulationParams, pkt_params: &PacketParams) -> u32 {
+    let sf = sf_value(mdltn_params.spreading_factor) as i32;
+    let cr = cr_denom(mdltn_params.coding_rate) as i32 - 4;
+    let pl = pkt_params.payload_length as i32;
+    let crc = pkt_params.crc_on as i32;
+    let header = if pkt_params.implicit_header {0} else {20};
+    let ldro = mdltn_params.low_data_rate_optimize != 0;
+    // Number of payload symbols, header included
+    let (num, den, pbl_extra_quarter) = if sf < 7 {
+        (8 * pl + 16 * crc - 4 * sf + header, 4 * sf, 25)
+    } else {
+        let den = if ldro {4 * (sf - 2)} else {4 * sf};
+        (8 * pl + 16 * crc - 4 * sf + 8 + header, den, 17)
+    };
+    let nb_sym_pld = 8 + (num.max(0) as u32).div_ceil(den as u32) * (cr as u32 + 4);
+    // Total duration in quarter of symbols: preamble + 4.25 (or 6.25) sync symbols + payload
+    let nb_quarter = 4 * pkt_params.preamble_length as u64 + pbl_extra_quarter + 4 * nb_sym_pld as u64;
+    // Tsym = 2^SF * div / 500kHz
+    let toa_us = (nb_quarter << sf) * bw_div(mdltn_params.bandwidth);
+    toa_us.div_ceil(2) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lora_phy::mod_params::CodingRate;
+
+    fn mdltn(sf: SpreadingFactor, ldro: bool) -> ModulationParams {
+        ModulationParams {
+            spreading_factor: sf,
+            bandwidth: Bandwidth::_125KHz,
+            coding_rate: CodingRate::_4_5,
+            low_data_rate_optimize: ldro as u8,
+            frequency_in_hz: 868_100_000,
+        }
+    }
+
+    fn pkt(payload_length: u8) -> PacketParams {
+        PacketParams {
+            preamble_length: 8,
+            implicit_header: false,
+            payload_length,
+            crc_on: true,
+            iq_inverted: false,
+        }
+    }
+
+    #[test]
+    fn time_on_air() {
+        assert_eq!(time_on_air_us(&mdltn(SpreadingFactor::_7, false), &pkt(10)), 41216);
+        assert_eq!(time_on_air_us(&mdltn(SpreadingFactor::_12, true), &pkt(51)), 2465792);
+    }
+
+    #[test]
+    fn rtc_steps() {
+        // 8 symbols of 1.024ms
+        assert_eq!(symbols_to_rtc_steps(8, SpreadingFactor::_7, Bandwidth::_125KHz), 269);
+        // 1 symbol of 32.768ms
+        assert_eq!(symbols_to_rtc_steps(1, SpreadingFactor::_12, Bandwidth::_125KHz), 1074);
+        assert_eq!(symbols_to_rtc_steps(u16::MAX, SpreadingFactor::_12, Bandwidth::_7KHz), RTC_TIMEOUT_MAX);
+    }
+
+    #[test]
+    fn ldro() {
+        assert!(!ldro_required(SpreadingFactor::_10, Bandwidth::_125KHz));
+        assert!(ldro_required(SpreadingFactor::_11, Bandwidth::_125KHz));
+        assert!(!ldro_required(SpreadingFactor::_11, Bandwidth::_250KHz));
+        assert!(ldro_required(SpreadingFactor::_12, Bandwidth::_250KHz));
+    }
+}